@@ -0,0 +1,57 @@
+use crate::board::Board;
+use crate::error::OthelloError;
+use crate::minimax::{SearchConfig, Searcher};
+use std::time::{Duration, Instant};
+
+/// Number of entries of the transposition table of the benchmark, as a power of 2
+const TABLE_BITS: u32 = 20;
+
+/// Fixed set of middle game positions, reached by always playing the same
+/// rank among the legal moves
+pub fn positions() -> Vec<Board> {
+    [(10, 2), (14, 3), (18, 5), (22, 7), (26, 11), (30, 13)]
+        .iter()
+        .map(|&(plies, step)| {
+            let mut board = Board::new();
+            for ply in 0..plies {
+                let moves = board.available_moves(None);
+                let m = (!moves.is_empty()).then(|| moves[ply * step % moves.len()]);
+                board.play(m).expect("Move should be legal");
+            }
+            board
+        })
+        .collect()
+}
+
+/// Search the positions with 1, 2, 4... threads up to `max_threads`, and print
+/// the time taken and the speedup over a single thread
+/// # Returns
+/// * `Err(OthelloError)` if a search fails
+pub fn smp_speedup(depth: u8, max_threads: usize) -> Result<(), OthelloError> {
+    let positions = positions();
+    let mut single = None;
+    let mut threads = 1;
+    while threads <= max_threads {
+        let config = SearchConfig {
+            threads,
+            ..SearchConfig::default()
+        };
+        let (mut time, mut nodes) = (Duration::ZERO, 0);
+        for board in &positions {
+            let mut searcher = Searcher::with_config(TABLE_BITS, config.clone())?;
+            let start = Instant::now();
+            nodes += searcher.search(board, depth)?.nodes;
+            time += start.elapsed();
+        }
+        let single = *single.get_or_insert(time);
+        println!(
+            "Threads: {}, Time: {:.3}s, Nodes: {}, Speedup: {:.2}",
+            threads,
+            time.as_secs_f64(),
+            nodes,
+            single.as_secs_f64() / time.as_secs_f64()
+        );
+        threads *= 2;
+    }
+    Ok(())
+}
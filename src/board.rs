@@ -1,6 +1,12 @@
-use crate::rules::{check_direction, is_legal_move, is_legal_move_with_gain};
+use crate::error::OthelloError;
+use crate::rules::{
+    corner_neighbours, flip_diagonal, get_flips, get_moves, is_legal_move, mirror_columns,
+    neighbours, square_coords, square_mask, stable_discs, CORNERS,
+};
 use ansi_term::{Colour, Colour::*, Style};
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Case {
     Empty,
@@ -16,6 +22,9 @@ pub enum EndState {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BoardState {
     Ongoing,
+    /// The player to move has no legal move and has to pass
+    MustPass,
+    /// Neither player can move
     Ended(EndState),
 }
 
@@ -29,10 +38,65 @@ impl Case {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Move in standard notation: the column from `a` to `h` then the row from 1 to 8,
+/// `f5` being `(4, 5)`, and `pa` a pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move(pub Option<(usize, usize)>);
+
+impl Move {
+    pub const PASS: Move = Move(None);
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some((row, col)) => write!(f, "{}{}", (b'a' + col as u8) as char, row + 1),
+            None => write!(f, "pa"),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = OthelloError;
+
+    /// Parse a move in either case, `pa` or `--` being a pass
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        if lower == "pa" || lower == "--" {
+            return Ok(Move::PASS);
+        }
+        match lower.as_bytes() {
+            &[col @ b'a'..=b'h', row @ b'1'..=b'8'] => {
+                Ok(Move(Some(((row - b'1') as usize, (col - b'a') as usize))))
+            }
+            _ => Err(OthelloError::Parse(format!("Invalid move: {}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct History {
-    pub moves: Vec<(usize, usize)>,
-    pub history: Vec<[[Case; 8]; 8]>,
+    /// Moves in the order they were played, `None` being a pass
+    pub moves: Vec<Option<(usize, usize)>>,
+    /// How to take back each of these moves
+    pub history: Vec<Undo>,
+}
+
+/// Record of a move returned by `Board::make_move`, enough to take it back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Undo {
+    /// Bit of the square played, 0 for a pass
+    pub square: u64,
+    /// Discs flipped by the move
+    pub flips: u64,
+}
+
+impl Undo {
+    /// Record of a pass
+    pub const PASS: Undo = Undo {
+        square: 0,
+        flips: 0,
+    };
 }
 
 impl Display for Case {
@@ -45,21 +109,119 @@ impl Display for Case {
     }
 }
 
+/// Board stored as two bitboards, bit `8 * row + col` being set
+/// when the corresponding player has a disc on that square
 #[derive(Clone, Debug)]
 pub struct Board {
-    pub cases: [[Case; 8]; 8],
+    player: u64,
+    opponent: u64,
+    turn: Case,
+    /// Zobrist key of the position, including the player to move
+    hash: u64,
     pub history: History,
 }
-pub const DIRECTIONS: [(i8, i8); 8] = [
-    (1, 0),
-    (1, 1),
-    (0, 1),
-    (-1, 1),
-    (-1, 0),
-    (-1, -1),
-    (0, -1),
-    (1, -1),
-];
+
+/// Step of the splitmix64 generator, used to fill the Zobrist keys at compile time
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn zobrist_keys() -> ([[u64; 64]; 2], u64) {
+    let mut keys = [[0; 64]; 2];
+    let mut state = 0x5eed;
+    let mut i = 0;
+    while i < 128 {
+        let (next, key) = splitmix64(state);
+        keys[i / 64][i % 64] = key;
+        state = next;
+        i += 1;
+    }
+    (keys, splitmix64(state).1)
+}
+
+/// Zobrist keys of a black (index 0) or white (index 1) disc on each square,
+/// and key xored in when white is to move
+const ZOBRIST: ([[u64; 64]; 2], u64) = zobrist_keys();
+
+#[inline]
+fn zobrist_color(color: Case) -> &'static [u64; 64] {
+    match color {
+        Case::White => &ZOBRIST.0[1],
+        _ => &ZOBRIST.0[0],
+    }
+}
+
+/// Xor of the keys of every disc of `discs` for one color
+#[inline]
+fn zobrist_discs(keys: &[u64; 64], mut discs: u64) -> u64 {
+    let mut hash = 0;
+    while discs != 0 {
+        hash ^= keys[discs.trailing_zeros() as usize];
+        discs &= discs - 1;
+    }
+    hash
+}
+
+/// One of the 8 symmetries of the board: the transposition along the a1-h8 diagonal
+/// if bit 2 is set, then the reversal of the columns if bit 0 is, then the one of the rows if bit 1 is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Symmetry(u8);
+
+impl Symmetry {
+    pub const IDENTITY: Symmetry = Symmetry(0);
+
+    /// Every symmetry, the identity first
+    pub fn all() -> impl Iterator<Item = Symmetry> {
+        (0..8).map(Symmetry)
+    }
+
+    /// Image of a set of squares
+    pub fn apply(self, mut bits: u64) -> u64 {
+        if self.0 & 4 != 0 {
+            bits = flip_diagonal(bits);
+        }
+        if self.0 & 1 != 0 {
+            bits = mirror_columns(bits);
+        }
+        if self.0 & 2 != 0 {
+            bits = bits.swap_bytes();
+        }
+        bits
+    }
+
+    /// Image of a square
+    pub fn apply_move(self, bmove: (usize, usize)) -> (usize, usize) {
+        square_coords(self.apply(square_mask(bmove)).trailing_zeros())
+    }
+
+    /// Symmetry undoing this one
+    pub fn inverse(self) -> Symmetry {
+        // Cases b1, c1 et a2 : seule l'identité les laisse en place
+        const ASYMMETRIC: u64 = 0x0106;
+        Symmetry::all()
+            .find(|inverse| inverse.apply(self.apply(ASYMMETRIC)) == ASYMMETRIC)
+            .unwrap()
+    }
+}
+
+/// Positions are equal when they have the same discs and player to move, whatever their history
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.player == other.player && self.opponent == other.opponent && self.turn == other.turn
+    }
+}
+
+impl Eq for Board {}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
 
 impl Board {
     /// Create a new board
@@ -67,81 +229,330 @@ impl Board {
     /// * A new board with start cases filled in
     pub fn new() -> Self {
         let mut board = Board {
-            cases: [[Case::Empty; 8]; 8],
+            // Black to move on d5 and e4, white on d4 and e5
+            player: square_mask((3, 4)) | square_mask((4, 3)),
+            opponent: square_mask((3, 3)) | square_mask((4, 4)),
+            turn: Case::Black,
+            hash: 0,
             history: History {
-                moves: Vec::<(usize, usize)>::with_capacity(60),
-                history: Vec::<[[Case; 8]; 8]>::with_capacity(60),
+                moves: Vec::<Option<(usize, usize)>>::with_capacity(60),
+                history: Vec::<Undo>::with_capacity(60),
             },
         };
-        board.cases[3][3] = Case::White;
-        board.cases[4][4] = Case::White;
-        board.cases[3][4] = Case::Black;
-        board.cases[4][3] = Case::Black;
+        board.hash = board.compute_zobrist();
+        board
+    }
+
+    /// Zobrist key of the position, maintained incrementally by the moves
+    #[inline]
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    /// Compute the Zobrist key of the position from scratch
+    fn compute_zobrist(&self) -> u64 {
+        let mut hash = zobrist_discs(zobrist_color(self.turn), self.player)
+            ^ zobrist_discs(zobrist_color(self.turn.opponent()), self.opponent);
+        if self.turn == Case::White {
+            hash ^= ZOBRIST.1;
+        }
+        hash
+    }
+
+    /// Update the Zobrist key for `undo`, made or taken back by `mover`
+    #[inline]
+    fn update_zobrist(&mut self, undo: Undo, mover: Case) {
+        let mover_keys = zobrist_color(mover);
+        let opponent_keys = zobrist_color(mover.opponent());
+        let mut flips = undo.flips;
+        while flips != 0 {
+            let square = flips.trailing_zeros() as usize;
+            self.hash ^= mover_keys[square] ^ opponent_keys[square];
+            flips &= flips - 1;
+        }
+        if undo.square != 0 {
+            self.hash ^= mover_keys[undo.square.trailing_zeros() as usize];
+        }
+        self.hash ^= ZOBRIST.1;
+    }
+
+    /// Image of the position by a symmetry, without history
+    pub fn transform(&self, symmetry: Symmetry) -> Board {
+        let mut board = Board {
+            player: symmetry.apply(self.player),
+            opponent: symmetry.apply(self.opponent),
+            turn: self.turn,
+            hash: 0,
+            history: History::default(),
+        };
+        board.hash = board.compute_zobrist();
         board
     }
 
+    /// Read a position written as its 64 squares from a1 to h8, `-` (or `.`) being empty,
+    /// `X` (or `*`) black and `O` white, followed by the side to move, `X` or `O`,
+    /// as in the FFO endgame suite: `--XXXXX--OOOXX-O...OXXXOX- O`
+    /// # Returns
+    /// * `Err(OthelloError::Parse)` if the string is not such a position
+    pub fn from_position(position: &str) -> Result<Board, OthelloError> {
+        let chars: Vec<char> = position
+            .trim()
+            .trim_end_matches(';')
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        if chars.len() != 65 {
+            return Err(OthelloError::Parse(format!(
+                "Invalid position: 64 squares and the side to move expected, found {} characters",
+                chars.len()
+            )));
+        }
+        let (mut black, mut white) = (0, 0);
+        for (square, &c) in chars[..64].iter().enumerate() {
+            match c.to_ascii_uppercase() {
+                '-' | '.' => {}
+                'X' | '*' => black |= 1 << square,
+                'O' => white |= 1 << square,
+                _ => {
+                    return Err(OthelloError::Parse(format!(
+                        "Invalid position: unknown square '{}' at {}",
+                        c,
+                        Move(Some(square_coords(square as u32)))
+                    )))
+                }
+            }
+        }
+        let turn = match chars[64].to_ascii_uppercase() {
+            'X' | '*' => Case::Black,
+            'O' => Case::White,
+            c => {
+                return Err(OthelloError::Parse(format!(
+                    "Invalid position: unknown side to move '{}'",
+                    c
+                )))
+            }
+        };
+        let (player, opponent) = if turn == Case::Black {
+            (black, white)
+        } else {
+            (white, black)
+        };
+        let mut board = Board {
+            player,
+            opponent,
+            turn,
+            hash: 0,
+            history: History::default(),
+        };
+        board.hash = board.compute_zobrist();
+        Ok(board)
+    }
+
+    /// Write the position as read by `from_position`
+    pub fn position(&self) -> String {
+        let mut position: String = (0..64)
+            .map(|square| match self.get(square_coords(square)) {
+                Case::Empty => '-',
+                Case::Black => 'X',
+                Case::White => 'O',
+            })
+            .collect();
+        position.push_str(if self.turn == Case::Black { " X" } else { " O" });
+        position
+    }
+
+    /// Representative of the position and of its symmetric images, the one with the smallest
+    /// discs of the player to move then of its opponent, which does not depend on the keys
+    /// # Returns
+    /// * The representative, and the symmetry leading to it from the position
+    pub fn canonical(&self) -> (Board, Symmetry) {
+        Symmetry::all()
+            .map(|symmetry| (self.transform(symmetry), symmetry))
+            .min_by_key(|(board, _)| (board.player, board.opponent))
+            .unwrap()
+    }
+
     /// Get the current turn
     pub fn get_turn(&self) -> Case {
-        let sum = self.score().0 + self.score().1;
-        if sum % 2 == 0 {
-            Case::Black
+        self.turn
+    }
+
+    /// Get the discs of a color as a bitboard
+    pub fn discs(&self, color: Case) -> u64 {
+        match color {
+            Case::Empty => !(self.player | self.opponent),
+            c if c == self.turn => self.player,
+            _ => self.opponent,
+        }
+    }
+
+    /// Get the content of a square
+    pub fn get(&self, case: (usize, usize)) -> Case {
+        let mask = square_mask(case);
+        if self.player & mask != 0 {
+            self.turn
+        } else if self.opponent & mask != 0 {
+            self.turn.opponent()
         } else {
-            Case::White
+            Case::Empty
+        }
+    }
+
+    /// Get the content of every square, indexed by row then column
+    pub fn cases(&self) -> [[Case; 8]; 8] {
+        let mut cases = [[Case::Empty; 8]; 8];
+        for (i, line) in cases.iter_mut().enumerate() {
+            for (j, case) in line.iter_mut().enumerate() {
+                *case = self.get((i, j));
+            }
         }
+        cases
     }
 
     /// Play a move on the board
     /// # Arguments
     /// * `bmove` - The move to make
     /// # Returns
-    /// * `Ok(())` if the move is legal
-    /// * `Err(String)` if the move is illegal
-    pub fn play_move(&mut self, bmove: &(usize, usize)) -> Result<BoardState, String> {
-        let color = self.get_turn();
-        if !is_legal_move(&self.cases, *bmove, &color) {
-            let mut s = String::new();
-            s.push_str("Illegal move : ");
-            s.push_str(&format!("{:?}", bmove));
-
-            return Err(s);
-        }
-        self.cases[bmove.0][bmove.1] = color;
-
-        for direction in DIRECTIONS {
-            if check_direction(
-                &self.cases,
-                (bmove.0 as i8, bmove.1 as i8),
-                direction,
-                &color,
-            ) {
-                let mut x = bmove.0 as i8 + direction.0;
-                let mut y = bmove.1 as i8 + direction.1;
-                while self.cases[x as usize][y as usize] == color.opponent() {
-                    self.cases[x as usize][y as usize] = color;
-                    x += direction.0;
-                    y += direction.1;
+    /// * `Ok(BoardState)` if the move is legal, describing what the next player has to do
+    /// * `Err(OthelloError)` if the move is illegal
+    pub fn play_move(&mut self, bmove: &(usize, usize)) -> Result<BoardState, OthelloError> {
+        self.play(Some(*bmove))
+    }
+
+    /// Pass the turn to the opponent
+    /// # Returns
+    /// * `Ok(BoardState)` if the player to move had no legal move
+    /// * `Err(OthelloError)` if the player can still move or the game is over
+    pub fn pass(&mut self) -> Result<BoardState, OthelloError> {
+        self.play(None)
+    }
+
+    /// Play a move or pass when `bmove` is `None`, recording it in the history
+    pub fn play(&mut self, bmove: Option<(usize, usize)>) -> Result<BoardState, OthelloError> {
+        let undo = self.make_move(bmove)?;
+        self.history.moves.push(bmove);
+        self.history.history.push(undo);
+
+        Ok(self.get_state())
+    }
+
+    /// Play a move or pass when `bmove` is `None`, without recording it in the history
+    /// # Arguments
+    /// * `bmove` - The move to make
+    /// # Returns
+    /// * `Ok(Undo)` if the move is legal, to be given back to `unmake_move`
+    /// * `Err(OthelloError)` if the move is illegal, telling why
+    pub fn make_move(&mut self, bmove: Option<(usize, usize)>) -> Result<Undo, OthelloError> {
+        let undo = match bmove {
+            Some(bmove) if is_legal_move(self.player, self.opponent, bmove) => {
+                self.undo_of(square_mask(bmove))
+            }
+            Some(bmove) if bmove.0 >= 8 || bmove.1 >= 8 => {
+                return Err(OthelloError::OutOfBounds(bmove))
+            }
+            Some(bmove) => {
+                self.can_move()?;
+                if (self.player | self.opponent) & square_mask(bmove) != 0 {
+                    return Err(OthelloError::Occupied(bmove));
                 }
+                return Err(OthelloError::NoFlips(bmove));
             }
+            None => match self.get_state() {
+                BoardState::MustPass => Undo::PASS,
+                BoardState::Ongoing => return Err(OthelloError::MovesAvailable),
+                BoardState::Ended(_) => return Err(OthelloError::GameOver),
+            },
+        };
+        self.make_move_unchecked(undo);
+        Ok(undo)
+    }
+
+    /// Apply a move already known to be legal, `undo` holding its square and flipped discs
+    #[inline]
+    pub fn make_move_unchecked(&mut self, undo: Undo) {
+        (self.player, self.opponent) = (
+            self.opponent ^ undo.flips,
+            self.player | undo.flips | undo.square,
+        );
+        self.update_zobrist(undo, self.turn);
+        self.turn = self.turn.opponent();
+    }
+
+    /// Take back the move described by `undo`, which must be the last one made
+    #[inline]
+    pub fn unmake_move(&mut self, undo: Undo) {
+        (self.player, self.opponent) = (
+            self.opponent ^ undo.flips ^ undo.square,
+            self.player | undo.flips,
+        );
+        self.turn = self.turn.opponent();
+        self.update_zobrist(undo, self.turn);
+    }
+
+    /// Returns the move record of playing `bmove`, a legal move of the player to move
+    #[inline]
+    pub fn undo_of(&self, bmove: u64) -> Undo {
+        Undo {
+            square: bmove,
+            flips: get_flips(self.player, self.opponent, bmove),
         }
-        self.history.moves.push(*bmove);
-        self.history.history.push(self.cases);
+    }
 
-        Ok(if self.available_moves(None).is_empty() {
-            match self.score() {
-                (x, y) if x > y => BoardState::Ended(EndState::Winner(Case::Black)),
-                (x, y) if x < y => BoardState::Ended(EndState::Winner(Case::White)),
-                _ => BoardState::Ended(EndState::Winner(Case::Empty)),
+    /// Count the positions reached after `depth` plies, a pass being a ply
+    /// and a finished game a leaf whatever the depth left
+    pub fn perft(&mut self, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut moves = self.moves_mask();
+        if moves == 0 {
+            if self.opponent_moves_mask() == 0 {
+                return 1;
             }
-        } else {
+            self.make_move_unchecked(Undo::PASS);
+            let count = self.perft(depth - 1);
+            self.unmake_move(Undo::PASS);
+            return count;
+        }
+        if depth == 1 {
+            return moves.count_ones() as u64;
+        }
+        let mut count = 0;
+        while moves != 0 {
+            let undo = self.undo_of(moves & moves.wrapping_neg());
+            moves &= moves - 1;
+            self.make_move_unchecked(undo);
+            count += self.perft(depth - 1);
+            self.unmake_move(undo);
+        }
+        count
+    }
+
+    /// Returns what the player to move can do in the current position
+    pub fn get_state(&self) -> BoardState {
+        if self.moves_mask() != 0 {
             BoardState::Ongoing
-        })
+        } else if self.opponent_moves_mask() != 0 {
+            BoardState::MustPass
+        } else {
+            BoardState::Ended(EndState::Winner(self.current_winner()))
+        }
     }
 
-    pub fn move_with_highest_gain(&self) -> Result<(usize, usize), String> {
-        let moves = self.available_moves_with_gain();
-        if moves.is_empty() {
-            return Err("No moves available".to_string());
+    /// Check that the player to move has a legal move
+    /// # Returns
+    /// * `Err(OthelloError::MustPass)` or `Err(OthelloError::GameOver)` otherwise
+    pub fn can_move(&self) -> Result<(), OthelloError> {
+        match self.get_state() {
+            BoardState::Ongoing => Ok(()),
+            BoardState::MustPass => Err(OthelloError::MustPass),
+            BoardState::Ended(_) => Err(OthelloError::GameOver),
         }
+    }
+
+    pub fn move_with_highest_gain(&self) -> Result<(usize, usize), OthelloError> {
+        self.can_move()?;
+        let moves = self.available_moves_with_gain();
         let mut highest_gain = 0;
         let mut highest_move = (0, 0);
         for (m, g) in moves {
@@ -153,55 +564,95 @@ impl Board {
         Ok(highest_move)
     }
 
+    /// Returns the set of legal moves of the player to move
+    #[inline]
+    pub fn moves_mask(&self) -> u64 {
+        get_moves(self.player, self.opponent)
+    }
+
+    /// Returns the set of legal moves of the player waiting for its turn
+    #[inline]
+    pub fn opponent_moves_mask(&self) -> u64 {
+        get_moves(self.opponent, self.player)
+    }
+
     /// Returns a vector of all the available moves for a given color
     /// # Arguments
     /// * `color` - The color of the player
     pub fn available_moves(&self, color: Option<Case>) -> Vec<(usize, usize)> {
-        let current_color = self.get_turn();
-        let color = color.unwrap_or(current_color);
-        let mut moves = Vec::new();
-        for i in 0..8 {
-            for j in 0..8 {
-                if is_legal_move(&self.cases, (i, j), &color) {
-                    moves.push((i, j));
-                }
-            }
+        let color = color.unwrap_or(self.turn);
+        let mut moves = if color == self.turn {
+            get_moves(self.player, self.opponent)
+        } else {
+            get_moves(self.opponent, self.player)
+        };
+        let mut res = Vec::with_capacity(moves.count_ones() as usize);
+        while moves != 0 {
+            res.push(square_coords(moves.trailing_zeros()));
+            moves &= moves - 1;
         }
-        moves
+        res
     }
 
     pub fn available_moves_with_gain(&self) -> Vec<((usize, usize), usize)> {
-        let mut moves = Vec::new();
-        let color = self.get_turn();
-        for i in 0..8 {
-            for j in 0..8 {
-                let (legal, gain) = is_legal_move_with_gain(&self.cases, (i, j), &color);
-                if legal {
-                    moves.push(((i, j), gain));
-                }
-            }
-        }
-        moves
+        self.available_moves(None)
+            .into_iter()
+            .map(|m| {
+                let flips = get_flips(self.player, self.opponent, square_mask(m));
+                (m, flips.count_ones() as usize)
+            })
+            .collect()
+    }
+
+    /// Returns the number of legal moves of a color, whoever's turn it is
+    pub fn mobility(&self, color: Case) -> u32 {
+        get_moves(self.discs(color), self.discs(color.opponent())).count_ones()
+    }
+
+    /// Returns the number of empty squares next to an opponent disc,
+    /// where a color may be able to play in the next moves
+    pub fn potential_mobility(&self, color: Case) -> u32 {
+        (neighbours(self.discs(color.opponent())) & self.discs(Case::Empty)).count_ones()
+    }
+
+    /// Returns the discs of a color next to an empty square, which give moves to the opponent
+    pub fn frontier(&self, color: Case) -> u64 {
+        self.discs(color) & neighbours(self.discs(Case::Empty))
+    }
+
+    /// Returns the number of corners taken by a color
+    pub fn corners(&self, color: Case) -> u32 {
+        (self.discs(color) & CORNERS).count_ones()
+    }
+
+    /// Returns the discs of a color on the X and C squares of the empty corners,
+    /// through which the opponent may take the corner
+    pub fn corner_neighbours(&self, color: Case) -> u64 {
+        corner_neighbours(self.discs(color), self.discs(Case::Empty))
+    }
+
+    /// Returns the discs of a color which can never be flipped again, whatever is played
+    pub fn stable_discs(&self, color: Case) -> u64 {
+        stable_discs(self.discs(color), self.discs(color.opponent()))
+    }
+
+    /// Returns the number of empty squares
+    #[inline]
+    pub fn empties(&self) -> u32 {
+        (!(self.player | self.opponent)).count_ones()
     }
 
     /// Returns the score of the board (black, white)
     pub fn score(&self) -> (usize, usize) {
-        let mut white = 0;
-        let mut black = 0;
-        for line in self.cases.iter() {
-            for case in line.iter() {
-                match case {
-                    Case::White => white += 1,
-                    Case::Black => black += 1,
-                    Case::Empty => (),
-                }
-            }
-        }
-        (black, white)
+        (
+            self.discs(Case::Black).count_ones() as usize,
+            self.discs(Case::White).count_ones() as usize,
+        )
     }
 
+    /// Returns true when neither player can move
     pub fn is_ended(&self) -> bool {
-        self.available_moves(None).is_empty()
+        matches!(self.get_state(), BoardState::Ended(_))
     }
 
     pub fn current_winner(&self) -> Case {
@@ -212,22 +663,42 @@ impl Board {
         }
     }
 
-    pub fn reset(&mut self, num: usize) {
-        if num == 0 || self.history.history.is_empty() {
-            return;
-        }
-        if num >= self.history.history.len() {
-            self.cases = Board::new().cases;
-            self.history.history.clear();
-            self.history.moves.clear();
-            return;
+    /// Replay a game from its transcript, the moves written one after the other
+    /// (`f5d6c3...`). The passes may be written as `pa` or left out.
+    /// # Returns
+    /// * `Err(OthelloError)` if a move cannot be read or is illegal
+    pub fn from_transcript(transcript: &str) -> Result<Board, OthelloError> {
+        let mut board = Board::new();
+        let chars: Vec<char> = transcript.chars().filter(|c| !c.is_whitespace()).collect();
+        for token in chars.chunks(2) {
+            let bmove: Move = token.iter().collect::<String>().parse()?;
+            if bmove.0.is_some() && board.get_state() == BoardState::MustPass {
+                board.pass()?;
+            }
+            board.play(bmove.0)?;
         }
-        self.cases = self.history.history[self.history.history.len() - num - 1];
-        // Remove the last num moves
+        Ok(board)
+    }
+
+    /// Moves of the game in standard notation, passes included
+    pub fn transcript(&self) -> String {
         self.history
-            .history
-            .truncate(self.history.history.len() - num);
-        self.history.moves.truncate(self.history.moves.len() - num);
+            .moves
+            .iter()
+            .map(|&bmove| Move(bmove).to_string())
+            .collect()
+    }
+
+    /// Take back the last `num` moves of the history
+    #[allow(dead_code)]
+    pub fn reset(&mut self, num: usize) {
+        for _ in 0..num {
+            let Some(undo) = self.history.history.pop() else {
+                return;
+            };
+            self.history.moves.pop();
+            self.unmake_move(undo);
+        }
     }
 }
 
@@ -242,10 +713,10 @@ impl Display for Board {
                 RGB(17, 153, 90)
             }
         }
-        let mut string = String::from("   1  2  3  4  5  6  7  8 \n");
+        let mut string = String::from("   a  b  c  d  e  f  g  h \n");
         let mut bg: u8 = 0;
-        for (i, line) in self.cases.iter().enumerate() {
-            string.push_str(&format!("{} ", (i as u8 + 65) as char)[..]);
+        for (i, line) in self.cases().iter().enumerate() {
+            string.push_str(&format!("{} ", i + 1)[..]);
             for case in line.iter() {
                 match case {
                     Case::Empty => {
@@ -294,12 +765,12 @@ fn play_move_test() {
     board
         .play_move(&(0, 0))
         .expect_err("Move should not be legal");
-    assert_eq!(board.cases[0][0], Case::Empty); // Check that the move was not made
+    assert_eq!(board.get((0, 0)), Case::Empty); // Check that the move was not made
                                                 // Check the initial board
-    assert_eq!(board.cases[3][3], Case::White);
-    assert_eq!(board.cases[4][4], Case::White);
-    assert_eq!(board.cases[3][4], Case::Black);
-    assert_eq!(board.cases[4][3], Case::Black);
+    assert_eq!(board.get((3, 3)), Case::White);
+    assert_eq!(board.get((4, 4)), Case::White);
+    assert_eq!(board.get((3, 4)), Case::Black);
+    assert_eq!(board.get((4, 3)), Case::Black);
     println!("{}", board);
 }
 
@@ -323,23 +794,267 @@ fn reset_test() {
     //println!("{}", board);
     board.reset(1);
     //println!("{}", board);
-    assert_eq!(board.cases[3][3], Case::White);
-    assert_eq!(board.cases[4][4], Case::White);
-    assert_eq!(board.cases[3][4], Case::Black);
-    assert_eq!(board.cases[4][3], Case::Black);
+    assert_eq!(board.get((3, 3)), Case::White);
+    assert_eq!(board.get((4, 4)), Case::White);
+    assert_eq!(board.get((3, 4)), Case::Black);
+    assert_eq!(board.get((4, 3)), Case::Black);
 }
 
-pub struct Move {
-    pub move_: (usize, usize),
+#[test]
+fn pass_test() {
+    let mut board = Board::new();
+    board.player = square_mask((0, 0)) | square_mask((7, 7));
+    board.opponent = square_mask((0, 1)) | square_mask((7, 6));
+    board.hash = board.compute_zobrist();
+    board.pass().expect_err("Black has moves available");
+    assert_eq!(board.play_move(&(0, 2)), Ok(BoardState::MustPass));
+    assert_eq!(board.get_turn(), Case::White);
+    assert!(!board.is_ended());
+    assert_eq!(board.pass(), Ok(BoardState::Ongoing));
+    assert_eq!(board.get_turn(), Case::Black);
+    assert_eq!(board.history.moves, vec![Some((0, 2)), None]);
+    assert_eq!(
+        board.play_move(&(7, 5)),
+        Ok(BoardState::Ended(EndState::Winner(Case::Black)))
+    );
+    board.pass().expect_err("The game is over");
+    board.reset(2);
+    assert_eq!(board.get_turn(), Case::White);
+    assert_eq!(board.get((7, 6)), Case::White);
 }
 
-impl Display for Move {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}{}",
-            self.move_.1 + 1,
-            (self.move_.0 as u8 + 97) as char,
+#[test]
+fn make_unmake_test() {
+    let mut board = Board::new();
+    let start = board.clone();
+    let mut undos = Vec::new();
+    while !board.is_ended() {
+        let bmove = board.available_moves(None).first().copied();
+        undos.push(board.make_move(bmove).expect("Move should be legal"));
+    }
+    assert!(board.history.moves.is_empty());
+    while let Some(undo) = undos.pop() {
+        board.unmake_move(undo);
+    }
+    assert_eq!(board.cases(), start.cases());
+    assert_eq!(board.get_turn(), Case::Black);
+}
+
+#[test]
+fn zobrist_test() {
+    let mut board = Board::new();
+    let start = board.zobrist();
+    // d3 c3 c4 and c4 c3 d3 transpose into the same position
+    for m in [(2, 3), (2, 2), (3, 2)] {
+        board.play_move(&m).expect("Move should be legal");
+        assert_eq!(board.zobrist(), board.compute_zobrist());
+    }
+    let mut other = Board::new();
+    for m in [(3, 2), (2, 2), (2, 3)] {
+        other.play_move(&m).expect("Move should be legal");
+    }
+    assert_eq!(board, other);
+    assert_eq!(board.zobrist(), other.zobrist());
+    board.reset(3);
+    assert_eq!(board.zobrist(), start);
+}
+
+#[test]
+fn features_test() {
+    // Same features square by square
+    fn around(board: &Board, (i, j): (usize, usize), case: Case) -> bool {
+        (-1..=1).any(|di: isize| {
+            (-1..=1).any(|dj: isize| {
+                let (i, j) = (i as isize + di, j as isize + dj);
+                (0..8).contains(&i)
+                    && (0..8).contains(&j)
+                    && board.get((i as usize, j as usize)) == case
+            })
+        })
+    }
+    let mut board = Board::new();
+    for _ in 0..40 {
+        for color in [Case::Black, Case::White] {
+            let squares = (0..8).flat_map(|i| (0..8).map(move |j| (i, j)));
+            let potential = squares
+                .clone()
+                .filter(|&m| board.get(m) == Case::Empty && around(&board, m, color.opponent()))
+                .count();
+            let frontier = squares
+                .filter(|&m| board.get(m) == color && around(&board, m, Case::Empty))
+                .count();
+            assert_eq!(board.potential_mobility(color) as usize, potential);
+            assert_eq!(board.frontier(color).count_ones() as usize, frontier);
+            assert_eq!(
+                board.mobility(color) as usize,
+                board.available_moves(Some(color)).len()
+            );
+            let stable = board.stable_discs(color);
+            assert_eq!(stable & !board.discs(color), 0);
+            assert_eq!(board.corners(color), (stable & CORNERS).count_ones());
+        }
+        let moves = board.available_moves(None);
+        board
+            .play((!moves.is_empty()).then(|| moves[moves.len() / 3]))
+            .expect("Move should be legal");
+    }
+}
+
+#[test]
+fn stable_discs_test() {
+    use rand::{seq::SliceRandom, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let mut board = Board::new();
+    while !board.is_ended() {
+        let stable = [Case::Black, Case::White].map(|color| (color, board.stable_discs(color)));
+        // Aucune suite de la partie ne doit retourner un pion stable
+        for _ in 0..20 {
+            let mut playout = board.clone();
+            while !playout.is_ended() {
+                let moves = playout.available_moves(None);
+                playout.play(moves.choose(&mut rng).copied()).unwrap();
+                for (color, discs) in stable {
+                    assert_eq!(playout.discs(color) & discs, discs);
+                }
+            }
+        }
+        let moves = board.available_moves(None);
+        board.play(moves.choose(&mut rng).copied()).unwrap();
+    }
+    // Everything is stable once the board is full
+    if board.empties() == 0 {
+        assert_eq!(
+            board.stable_discs(Case::Black) | board.stable_discs(Case::White),
+            u64::MAX
+        );
+    }
+}
+
+#[test]
+fn symmetry_test() {
+    let mut board = Board::new();
+    for m in [(2, 3), (2, 2), (3, 2), (2, 4), (1, 4)] {
+        board.play_move(&m).expect("Move should be legal");
+    }
+    let moves = board.available_moves(None);
+    let (canonical, _) = board.canonical();
+    let mut images = Vec::new();
+    for symmetry in Symmetry::all() {
+        let image = board.transform(symmetry);
+        assert_eq!(image.transform(symmetry.inverse()), board);
+        // The moves follow the discs
+        let mut expected: Vec<_> = moves.iter().map(|&m| symmetry.apply_move(m)).collect();
+        expected.sort_unstable();
+        assert_eq!(image.available_moves(None), expected);
+        // Every image has the same representative
+        let (representative, to_canonical) = image.canonical();
+        assert_eq!(representative, canonical);
+        assert_eq!(image.transform(to_canonical), canonical);
+        images.push(image.zobrist());
+    }
+    images.sort_unstable();
+    images.dedup();
+    assert_eq!(images.len(), 8);
+    assert_eq!(Symmetry(4).apply_move((0, 1)), (1, 0));
+    assert_eq!(Symmetry(1).apply_move((0, 1)), (0, 6));
+    assert_eq!(Symmetry(2).apply_move((0, 1)), (7, 1));
+    // The start position is its own image by 4 symmetries
+    let start = Board::new();
+    let fixed = Symmetry::all().filter(|&s| start.transform(s) == start);
+    assert_eq!(fixed.count(), 4);
+}
+
+#[test]
+fn notation_test() {
+    assert_eq!("f5".parse(), Ok(Move(Some((4, 5)))));
+    assert_eq!("A1".parse(), Ok(Move(Some((0, 0)))));
+    assert_eq!("PA".parse(), Ok(Move::PASS));
+    for invalid in ["", "f", "i1", "a9", "a0", "5f", "f5d6"] {
+        assert!(invalid.parse::<Move>().is_err());
+    }
+    for square in 0..64 {
+        let bmove = Move(Some(square_coords(square)));
+        assert_eq!(bmove.to_string().parse(), Ok(bmove));
+    }
+
+    let board = Board::from_transcript("f5d6c3d3c4").unwrap();
+    assert_eq!(board.transcript(), "f5d6c3d3c4");
+    assert_eq!(board.history.moves[0], Some((4, 5)));
+    assert_eq!(board.get_turn(), Case::White);
+    assert!(Board::from_transcript("f5f5").is_err());
+    assert!(Board::from_transcript("f5d").is_err());
+
+    // Passes are written in the transcript, and may be left out when reading it
+    let mut game = Board::new();
+    while !game.is_ended() {
+        let moves = game.available_moves(None);
+        game.play(moves.last().copied()).unwrap();
+    }
+    let transcript = game.transcript();
+    assert!(transcript.contains("pa"));
+    let replayed = Board::from_transcript(&transcript).unwrap();
+    assert_eq!(replayed.history.moves, game.history.moves);
+    let replayed = Board::from_transcript(&transcript.replace("pa", "")).unwrap();
+    assert_eq!(replayed.history.moves, game.history.moves);
+    assert!(replayed.is_ended());
+}
+
+#[test]
+fn position_test() {
+    let start = Board::new();
+    let position = start.position();
+    assert_eq!(
+        position,
+        format!(
+            "{}-OX{}XO-{} X",
+            "-".repeat(26),
+            "-".repeat(6),
+            "-".repeat(26)
         )
+    );
+    assert_eq!(Board::from_position(&position).unwrap(), start);
+
+    let mut board = Board::from_transcript("f5d6c3d3c4f4").unwrap();
+    let loaded = Board::from_position(&board.position()).unwrap();
+    assert_eq!(loaded, board);
+    assert_eq!(loaded.zobrist(), board.zobrist());
+    assert_eq!(loaded.available_moves(None), board.available_moves(None));
+    // Other symbols, case, and spaces in the diagram
+    let written = board.position().replace('-', ".").replace('X', "*");
+    let loaded = Board::from_position(&format!("{};", written.to_lowercase())).unwrap();
+    assert_eq!(loaded, board);
+    board.play_move(&(4, 2)).unwrap();
+    assert_eq!(board.position().chars().last(), Some('O'));
+
+    for invalid in [
+        "",
+        &position[..64],
+        &position.replace(" X", " Y"),
+        &position.replacen('-', "#", 1),
+        &format!("-{}", position),
+    ] {
+        assert!(Board::from_position(invalid).is_err());
     }
 }
+
+#[test]
+fn perft_test() {
+    let mut board = Board::new();
+    // The shortest games end at depth 9
+    let expected = [1, 4, 12, 56, 244, 1396, 8200, 55092, 390216, 3005288];
+    for (depth, &count) in expected.iter().enumerate() {
+        assert_eq!(board.perft(depth as u8), count, "depth {}", depth);
+    }
+    assert_eq!(board, Board::new());
+    assert_eq!(board.zobrist(), Board::new().zobrist());
+
+    // Black has to pass, White's only move ends the game
+    let mut board = Board::from_position(&format!("OX{} X", "-".repeat(62))).unwrap();
+    assert_eq!(board.perft(1), 1);
+    assert_eq!(board.perft(2), 1);
+    assert_eq!(board.perft(5), 1);
+    board.pass().unwrap();
+    assert_eq!(board.perft(1), 1);
+    board.play_move(&(0, 2)).unwrap();
+    assert_eq!(board.perft(3), 1);
+}
@@ -0,0 +1,313 @@
+use crate::board::{Board, Case, Symmetry};
+use crate::endgame::{EndgameSolver, DEFAULT_SOLVE_EMPTIES};
+use crate::error::{LoadError, OthelloError};
+use crate::evaluation::EvaluatorKind;
+use crate::minimax::{SearchConfig, Searcher};
+use crate::rules::square_coords;
+use fxhash::FxHashMap;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Moves which were played fewer times are not chosen from the book
+const MIN_GAMES: u32 = 2;
+/// Book moves this many discs worse than the best one on average are never chosen
+const TOLERANCE: f64 = 4.;
+/// Probability of a random move in the book part of the games building the book
+const EXPLORATION_RATE: f64 = 0.2;
+/// Number of games played between two updates of the book while building it
+const BUILD_BATCH: usize = 64;
+
+/// How a player picks its move among the moves of the book
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookChoice {
+    /// The move with the best average result
+    Best,
+    /// A random move among the good enough ones, the most played being the most likely
+    Weighted,
+}
+
+/// Results of the games where a move of the book was played
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BookMove {
+    pub bmove: (usize, usize),
+    pub games: u32,
+    /// Sum of the final disc differentials of these games, for the player of the move
+    pub score: i64,
+}
+
+impl BookMove {
+    pub fn average(&self) -> f64 {
+        self.score as f64 / self.games.max(1) as f64
+    }
+}
+
+/// Discs of the player to move and of its opponent, keying the positions of the book
+fn key(board: &Board) -> (u64, u64) {
+    let color = board.get_turn();
+    (board.discs(color), board.discs(color.opponent()))
+}
+
+/// Position of the book and move standing for a move of the board. When the position
+/// is symmetric, the moves which are images of one another share one move of the book.
+fn canonical_move(board: &Board, bmove: (usize, usize)) -> ((u64, u64), (usize, usize)) {
+    let (canonical, _) = board.canonical();
+    let bmove = Symmetry::all()
+        .filter(|&symmetry| board.transform(symmetry) == canonical)
+        .map(|symmetry| symmetry.apply_move(bmove))
+        .min()
+        .unwrap();
+    (key(&canonical), bmove)
+}
+
+/// Opening moves with the results of the games they were played in, the symmetric
+/// positions sharing the entry of their canonical form
+#[derive(Default)]
+pub struct Book {
+    positions: FxHashMap<(u64, u64), Vec<BookMove>>,
+}
+
+impl Book {
+    pub fn new() -> Self {
+        Book::default()
+    }
+
+    /// Number of positions of the book
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Read a book written by `save`
+    /// # Returns
+    /// * `Err(LoadError::Invalid)` with `OthelloError::Parse` if a line is malformed
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let mut book = Book::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let invalid = || OthelloError::Parse(format!("Invalid book line: {}", line));
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [player, opponent, square, games, score] = fields[..] else {
+                return Err(invalid().into());
+            };
+            let player = u64::from_str_radix(player, 16).map_err(|_| invalid())?;
+            let opponent = u64::from_str_radix(opponent, 16).map_err(|_| invalid())?;
+            let square = square.parse::<u32>().ok().filter(|&s| s < 64);
+            let book_move = BookMove {
+                bmove: square_coords(square.ok_or_else(invalid)?),
+                games: games.parse().map_err(|_| invalid())?,
+                score: score.parse().map_err(|_| invalid())?,
+            };
+            book.positions
+                .entry((player, opponent))
+                .or_default()
+                .push(book_move);
+        }
+        Ok(book)
+    }
+
+    /// Write the book, one move of a position per line
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut positions: Vec<_> = self.positions.iter().collect();
+        positions.sort_unstable_by_key(|(position, _)| **position);
+        for ((player, opponent), moves) in positions {
+            for m in moves {
+                let square = m.bmove.0 * 8 + m.bmove.1;
+                writeln!(
+                    file,
+                    "{:016x} {:016x} {} {} {}",
+                    player, opponent, square, m.games, m.score
+                )?;
+            }
+        }
+        file.flush()
+    }
+
+    /// Moves of the book in a position, placed back on the board as it is
+    pub fn moves(&self, board: &Board) -> Vec<BookMove> {
+        let (canonical, symmetry) = board.canonical();
+        let back = symmetry.inverse();
+        self.positions
+            .get(&key(&canonical))
+            .map_or(Vec::new(), |moves| {
+                moves
+                    .iter()
+                    .map(|m| BookMove {
+                        bmove: back.apply_move(m.bmove),
+                        ..*m
+                    })
+                    .collect()
+            })
+    }
+
+    /// Pick a move of the book
+    /// # Returns
+    /// * `None` if the position is out of the book, or none of its moves was played enough
+    pub fn choose(&self, board: &Board, choice: BookChoice) -> Option<(usize, usize)> {
+        let moves: Vec<BookMove> = self
+            .moves(board)
+            .into_iter()
+            .filter(|m| m.games >= MIN_GAMES)
+            .collect();
+        let best = moves
+            .iter()
+            .max_by(|a, b| a.average().total_cmp(&b.average()))?;
+        match choice {
+            BookChoice::Best => Some(best.bmove),
+            BookChoice::Weighted => {
+                let good: Vec<&BookMove> = moves
+                    .iter()
+                    .filter(|m| m.average() >= best.average() - TOLERANCE)
+                    .collect();
+                good.choose_weighted(&mut thread_rng(), |m| m.games)
+                    .ok()
+                    .map(|m| m.bmove)
+            }
+        }
+    }
+
+    /// Add the result of a game to the moves of its opening
+    /// # Arguments
+    /// * `moves` - The moves of the whole game, `None` being a pass
+    /// * `plies` - The number of moves of the opening
+    /// # Returns
+    /// * `Err(OthelloError)` if a move of the game is illegal, the book being left unchanged
+    pub fn add_game(
+        &mut self,
+        moves: &[Option<(usize, usize)>],
+        plies: usize,
+    ) -> Result<(), OthelloError> {
+        let mut board = Board::new();
+        let mut opening = Vec::new();
+        for &bmove in moves {
+            if let (Some(bmove), true) = (bmove, opening.len() < plies) {
+                opening.push((canonical_move(&board, bmove), board.get_turn()));
+            }
+            board.play(bmove)?;
+        }
+        let (black, white) = board.score();
+        for ((position, bmove), color) in opening {
+            let score = if color == Case::Black {
+                black as i64 - white as i64
+            } else {
+                white as i64 - black as i64
+            };
+            let moves = self.positions.entry(position).or_default();
+            match moves.iter_mut().find(|m| m.bmove == bmove) {
+                Some(m) => {
+                    m.games += 1;
+                    m.score += score;
+                }
+                None => moves.push(BookMove {
+                    bmove,
+                    games: 1,
+                    score,
+                }),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Expand the book with self-play games: the opening follows the book with some
+/// random moves, and a minimax search picks every move out of the book
+/// # Arguments
+/// * `games` - The number of games to play
+/// * `depth` - The depth of the searches
+/// * `plies` - The number of moves of the opening kept in the book
+/// # Returns
+/// * `Err(OthelloError)` if a search fails, the games of the batch being lost
+pub fn build(book: &mut Book, games: usize, depth: u8, plies: usize) -> Result<(), OthelloError> {
+    let config = SearchConfig {
+        evaluator: EvaluatorKind::Features,
+        ..SearchConfig::default()
+    };
+    for batch in (0..games).step_by(BUILD_BATCH) {
+        let played: Vec<Vec<Option<(usize, usize)>>> = (batch..games.min(batch + BUILD_BATCH))
+            .into_par_iter()
+            .map(|_| {
+                let mut rng = thread_rng();
+                let mut searcher = Searcher::with_config(16, config.clone())?;
+                let mut board = Board::new();
+                while !board.is_ended() {
+                    let moves = board.available_moves(None);
+                    let in_opening = board.history.moves.len() < plies && !moves.is_empty();
+                    let bmove = if in_opening && rng.gen_bool(EXPLORATION_RATE) {
+                        moves.choose(&mut rng).copied()
+                    } else if let (true, Some(m)) =
+                        (in_opening, book.choose(&board, BookChoice::Best))
+                    {
+                        Some(m)
+                    } else if board.empties() <= DEFAULT_SOLVE_EMPTIES as u32 {
+                        EndgameSolver::new().best_move(&board).0
+                    } else {
+                        searcher.search(&board, depth)?.best_move
+                    };
+                    board.play(bmove)?;
+                }
+                Ok(board.history.moves)
+            })
+            .collect::<Result<_, OthelloError>>()?;
+        for game in &played {
+            book.add_game(game, plies)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn book_test() {
+    let mut book = Book::new();
+    let mut board = Board::new();
+    assert_eq!(book.choose(&board, BookChoice::Best), None);
+    // The four first moves are symmetric: they all share one entry
+    for (game, first) in board.available_moves(None).into_iter().enumerate() {
+        let mut moves = vec![Some(first)];
+        let mut line = board.clone();
+        line.play_move(&first).unwrap();
+        while !line.is_ended() {
+            let m = line.available_moves(None).first().copied();
+            line.play(m).unwrap();
+            moves.push(m);
+        }
+        book.add_game(&moves, 2).unwrap();
+        assert_eq!(book.len(), 2);
+        assert_eq!(book.moves(&board)[0].games, game as u32 + 1);
+    }
+    let moves = book.moves(&board);
+    assert_eq!(moves.len(), 1);
+    assert_eq!(moves[0].games, 4);
+    let bmove = book.choose(&board, BookChoice::Weighted).unwrap();
+    assert!(board.available_moves(None).contains(&bmove));
+    board.play_move(&bmove).unwrap();
+
+    let path = std::env::temp_dir().join("othello_book_test.txt");
+    book.save(&path).unwrap();
+    let loaded = Book::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), book.len());
+    assert_eq!(loaded.moves(&board), book.moves(&board));
+    assert_eq!(
+        book.add_game(&[Some((2, 3)), Some((0, 0))], 2),
+        Err(OthelloError::NoFlips((0, 0)))
+    );
+    assert_eq!(book.len(), loaded.len());
+
+    // The positions are keyed by their discs alone: d3 played 4 times from the start
+    std::fs::write(&path, "0000000810000000 0000001008000000 19 4 34\n").unwrap();
+    let loaded = Book::load(&path).unwrap();
+    assert_eq!(loaded.choose(&Board::new(), BookChoice::Best), Some((2, 3)));
+
+    // A malformed book is told apart from a missing one
+    std::fs::write(&path, "0000000810000000 19 4 34\n").unwrap();
+    let malformed = Book::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        malformed,
+        Err(LoadError::Invalid(OthelloError::Parse(_)))
+    ));
+    assert!(matches!(Book::load(&path), Err(LoadError::Io(_))));
+}
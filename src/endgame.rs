@@ -0,0 +1,335 @@
+use crate::board::Board;
+use crate::rules::{get_flips, get_moves, square_coords, stable_discs};
+
+/// Number of empty squares from which the players solve the game, a few tenths of a second at most
+pub const DEFAULT_SOLVE_EMPTIES: u8 = 14;
+
+/// Number of empty squares from which MCTS looks for a forced win or draw
+pub const DEFAULT_WLD_EMPTIES: u8 = 16;
+
+/// Below this number of empty squares the moves are no longer sorted by mobility,
+/// computing it costing more than the nodes it saves
+const FASTEST_FIRST_EMPTIES: u32 = 7;
+
+/// Quadrants of the board, the last empty square of a region being better played by oneself
+const QUADRANTS: [u64; 4] = [
+    0x0000_0000_0f0f_0f0f,
+    0x0000_0000_f0f0_f0f0,
+    0x0f0f_0f0f_0000_0000,
+    0xf0f0_f0f0_0000_0000,
+];
+
+/// Final disc differential of a game over for the player to move,
+/// the empty squares going to the winner
+#[inline]
+pub fn final_score(player: u64, opponent: u64) -> i32 {
+    let diff = player.count_ones() as i32 - opponent.count_ones() as i32;
+    let empties = 64 - (player | opponent).count_ones() as i32;
+    match diff {
+        d if d > 0 => d + empties,
+        d if d < 0 => d - empties,
+        _ => 0,
+    }
+}
+
+/// Squares of the regions holding an odd number of empties, searched first
+#[inline]
+fn odd_regions(empty: u64) -> u64 {
+    QUADRANTS
+        .iter()
+        .filter(|&&quadrant| !(empty & quadrant).count_ones().is_multiple_of(2))
+        .fold(0, |odd, quadrant| odd | quadrant)
+}
+
+/// Result of a game with perfect play, for the player to move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// Perfect play search computing the exact final disc differential, working on
+/// the bare bitboards without any evaluation nor hashing
+pub struct EndgameSolver {
+    pub nodes: u64,
+}
+
+impl EndgameSolver {
+    pub fn new() -> Self {
+        EndgameSolver { nodes: 0 }
+    }
+
+    /// Exact final disc differential of the position for the player to move
+    #[allow(dead_code)]
+    pub fn solve(&mut self, board: &Board) -> i32 {
+        self.best_move(board).1
+    }
+
+    /// Find a move leading to the best final score with perfect play
+    /// # Returns
+    /// * The move, `None` if the player has to pass, and the final disc differential
+    pub fn best_move(&mut self, board: &Board) -> (Option<(usize, usize)>, i32) {
+        let color = board.get_turn();
+        let (player, opponent) = (board.discs(color), board.discs(color.opponent()));
+        self.search_root(player, opponent, -64, 64)
+    }
+
+    /// Find whether the position is won, drawn or lost, which only takes null-window
+    /// searches around zero and is much faster than computing the final score
+    /// # Returns
+    /// * The outcome, and a move achieving it unless the position is lost or the player has to pass
+    pub fn solve_wld(&mut self, board: &Board) -> (Outcome, Option<(usize, usize)>) {
+        let color = board.get_turn();
+        let (player, opponent) = (board.discs(color), board.discs(color.opponent()));
+        let (bmove, score) = self.search_root(player, opponent, 0, 1);
+        if score > 0 {
+            return (Outcome::Win, bmove);
+        }
+        let (bmove, score) = self.search_root(player, opponent, -1, 0);
+        if score == 0 {
+            (Outcome::Draw, bmove)
+        } else {
+            (Outcome::Loss, None)
+        }
+    }
+
+    /// Search the root with the window (alpha, beta), keeping the best move
+    pub fn search_root(
+        &mut self,
+        player: u64,
+        opponent: u64,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (Option<(usize, usize)>, i32) {
+        self.nodes += 1;
+        let moves = get_moves(player, opponent);
+        if moves == 0 {
+            return (None, self.alpha_beta(player, opponent, alpha, beta));
+        }
+        let mut best = (None, -65);
+        for square in self.sort_moves(player, opponent, moves) {
+            let flips = get_flips(player, opponent, square);
+            let val = -self.alpha_beta(opponent ^ flips, player | flips | square, -beta, -alpha);
+            if val > best.1 {
+                best = (Some(square_coords(square.trailing_zeros())), val);
+                alpha = alpha.max(val);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// Fail-soft alpha-beta returning the final disc differential for `player`
+    pub fn alpha_beta(&mut self, player: u64, opponent: u64, alpha: i32, beta: i32) -> i32 {
+        let empty = !(player | opponent);
+        if empty.count_ones() < FASTEST_FIRST_EMPTIES {
+            return self.alpha_beta_parity(player, opponent, alpha, beta, false);
+        }
+        self.nodes += 1;
+        // Le joueur ne peut pas gagner plus que ce que les pions stables adverses lui laissent
+        if alpha >= 64 - 2 * opponent.count_ones() as i32 {
+            let bound = 64 - 2 * stable_discs(opponent, player).count_ones() as i32;
+            if bound <= alpha {
+                return bound;
+            }
+        }
+        let moves = get_moves(player, opponent);
+        if moves == 0 {
+            if get_moves(opponent, player) == 0 {
+                return final_score(player, opponent);
+            }
+            return -self.alpha_beta(opponent, player, -beta, -alpha);
+        }
+        let mut alpha = alpha;
+        let mut best = -65;
+        for square in self.sort_moves(player, opponent, moves) {
+            let flips = get_flips(player, opponent, square);
+            let val = -self.alpha_beta(opponent ^ flips, player | flips | square, -beta, -alpha);
+            if val > best {
+                best = val;
+                alpha = alpha.max(val);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// Search of the last empty squares, going through them directly instead of generating
+    /// the moves, the squares of the regions with odd parity first
+    fn alpha_beta_parity(
+        &mut self,
+        player: u64,
+        opponent: u64,
+        mut alpha: i32,
+        beta: i32,
+        passed: bool,
+    ) -> i32 {
+        self.nodes += 1;
+        let empty = !(player | opponent);
+        if empty == 0 {
+            return final_score(player, opponent);
+        }
+        let odd = odd_regions(empty);
+        let mut best = -65;
+        for mut squares in [empty & odd, empty & !odd] {
+            while squares != 0 {
+                let square = squares & squares.wrapping_neg();
+                squares ^= square;
+                let flips = get_flips(player, opponent, square);
+                if flips == 0 {
+                    continue;
+                }
+                let val = -self.alpha_beta_parity(
+                    opponent ^ flips,
+                    player | flips | square,
+                    -beta,
+                    -alpha,
+                    false,
+                );
+                if val > best {
+                    best = val;
+                    alpha = alpha.max(val);
+                    if alpha >= beta {
+                        return best;
+                    }
+                }
+            }
+        }
+        if best == -65 {
+            // Aucun coup : on passe, ou la partie est finie si l'adversaire vient de passer
+            if passed {
+                return final_score(player, opponent);
+            }
+            return -self.alpha_beta_parity(opponent, player, -beta, -alpha, true);
+        }
+        best
+    }
+
+    /// Sort the moves by the mobility they leave to the opponent (fastest-first),
+    /// then by the parity of their region
+    fn sort_moves(&self, player: u64, opponent: u64, mut moves: u64) -> SortedMoves {
+        let odd = odd_regions(!(player | opponent));
+        let mut sorted = SortedMoves {
+            moves: [(0, 0); 64],
+            len: 0,
+            next: 0,
+        };
+        while moves != 0 {
+            let square = moves & moves.wrapping_neg();
+            moves ^= square;
+            let flips = get_flips(player, opponent, square);
+            let mobility = get_moves(opponent ^ flips, player | flips | square).count_ones();
+            let key = mobility * 2 + u32::from(square & odd == 0);
+            sorted.moves[sorted.len] = (key, square);
+            sorted.len += 1;
+        }
+        sorted.moves[..sorted.len].sort_unstable();
+        sorted
+    }
+}
+
+/// Moves sorted by `EndgameSolver::sort_moves`, kept on the stack
+struct SortedMoves {
+    moves: [(u32, u64); 64],
+    len: usize,
+    next: usize,
+}
+
+impl Iterator for SortedMoves {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.next == self.len {
+            return None;
+        }
+        self.next += 1;
+        Some(self.moves[self.next - 1].1)
+    }
+}
+
+/// Position reached by always playing the move at a third of the legal moves,
+/// once `empties` squares are left
+#[cfg(test)]
+pub fn endgame_position(empties: u32) -> Board {
+    let mut board = Board::new();
+    while board.empties() > empties {
+        let moves = board.available_moves(None);
+        match moves.get(moves.len() / 3) {
+            Some(m) => board.play_move(m).expect("Move should be legal"),
+            None => board.pass().expect("Pass should be legal"),
+        };
+    }
+    board
+}
+
+#[test]
+fn wld_solver_test() {
+    let sign_outcome = |score: i32| match score.signum() {
+        1 => Outcome::Win,
+        0 => Outcome::Draw,
+        _ => Outcome::Loss,
+    };
+    let mut board = endgame_position(12);
+    while !board.is_ended() {
+        let (outcome, bmove) = EndgameSolver::new().solve_wld(&board);
+        let exact = EndgameSolver::new().solve(&board);
+        assert_eq!(outcome, sign_outcome(exact));
+        if let Some(bmove) = bmove {
+            let mut after = board.clone();
+            after
+                .play_move(&bmove)
+                .expect("Proving move should be legal");
+            let score = -EndgameSolver::new().solve(&after);
+            assert_eq!(sign_outcome(score), outcome);
+        }
+        let bmove = EndgameSolver::new().best_move(&board).0;
+        board.play(bmove).expect("Best move should be legal");
+    }
+}
+
+#[test]
+fn endgame_solver_test() {
+    // Plain negamax over the whole end of the game
+    fn negamax(board: &mut Board) -> i32 {
+        let color = board.get_turn();
+        if board.is_ended() {
+            return final_score(board.discs(color), board.discs(color.opponent()));
+        }
+        let moves = board.available_moves(None);
+        if moves.is_empty() {
+            let undo = board.make_move(None).unwrap();
+            let val = -negamax(board);
+            board.unmake_move(undo);
+            return val;
+        }
+        let mut best = -65;
+        for m in moves {
+            let undo = board.make_move(Some(m)).unwrap();
+            best = best.max(-negamax(board));
+            board.unmake_move(undo);
+        }
+        best
+    }
+
+    let mut board = endgame_position(9);
+    for _ in 0..4 {
+        let expected = negamax(&mut board.clone());
+        let mut solver = EndgameSolver::new();
+        let (best_move, score) = solver.best_move(&board);
+        assert_eq!(score, expected);
+        let mut after = board.clone();
+        after.play(best_move).expect("Best move should be legal");
+        let color = after.get_turn();
+        let (player, opponent) = (after.discs(color), after.discs(color.opponent()));
+        assert_eq!(-solver.alpha_beta(player, opponent, -64, 64), expected);
+        board = after;
+        if board.is_ended() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::board::Move;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Why a move, a position, a game record or a player was refused
+#[derive(Clone, Debug, PartialEq)]
+pub enum OthelloError {
+    /// The square of the move already holds a disc
+    Occupied((usize, usize)),
+    /// The move flips no disc of the opponent
+    NoFlips((usize, usize)),
+    /// The move is off the board
+    OutOfBounds((usize, usize)),
+    /// Neither player can move anymore
+    GameOver,
+    /// The player to move has no legal move and has to pass
+    MustPass,
+    /// The player to move tried to pass while having legal moves
+    MovesAvailable,
+    /// Malformed move, position or game record
+    Parse(String),
+    /// The weights of the pattern evaluator cannot be read
+    Weights(String),
+    /// A manual player was asked to play games without a terminal
+    ManualInAutomatic,
+    /// The input of a manual player cannot be read
+    Io(String),
+    /// An engine failed to find a move
+    Engine(String),
+}
+
+impl Display for OthelloError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OthelloError::Occupied(m) => {
+                write!(f, "Illegal move {}: the square is taken", Move(Some(*m)))
+            }
+            OthelloError::NoFlips(m) => {
+                write!(f, "Illegal move {}: it flips no disc", Move(Some(*m)))
+            }
+            OthelloError::OutOfBounds((row, col)) => {
+                write!(f, "Illegal move ({}, {}): off the board", row, col)
+            }
+            OthelloError::GameOver => write!(f, "The game is over"),
+            OthelloError::MustPass => write!(f, "The player to move has to pass"),
+            OthelloError::MovesAvailable => write!(f, "Illegal pass: moves are available"),
+            OthelloError::Parse(message) => write!(f, "{}", message),
+            OthelloError::Weights(message) => write!(f, "Cannot read the weights {}", message),
+            OthelloError::ManualInAutomatic => {
+                write!(f, "Manual players are not supported in automatic games")
+            }
+            OthelloError::Io(message) => write!(f, "Cannot read the input: {}", message),
+            OthelloError::Engine(message) => write!(f, "The engine failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for OthelloError {}
+
+/// Why a file of games or a book cannot be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file cannot be read
+    Io(io::Error),
+    /// The file was read, but its content is malformed
+    Invalid(OthelloError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Invalid(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<OthelloError> for LoadError {
+    fn from(e: OthelloError) -> Self {
+        LoadError::Invalid(e)
+    }
+}
+
+#[test]
+fn errors_test() {
+    use crate::board::Board;
+    use crate::evaluation::EvaluatorKind;
+    use crate::player::{Player, Strategy};
+
+    let mut board = Board::new();
+    assert_eq!(board.play_move(&(0, 0)), Err(OthelloError::NoFlips((0, 0))));
+    assert_eq!(
+        board.play_move(&(3, 3)),
+        Err(OthelloError::Occupied((3, 3)))
+    );
+    assert_eq!(
+        board.play_move(&(8, 0)),
+        Err(OthelloError::OutOfBounds((8, 0)))
+    );
+    assert_eq!(board.pass(), Err(OthelloError::MovesAvailable));
+    assert_eq!(board, Board::new());
+    assert!(matches!("i9".parse::<Move>(), Err(OthelloError::Parse(_))));
+    assert!(matches!(
+        Board::from_transcript("f5f5"),
+        Err(OthelloError::Occupied((4, 5)))
+    ));
+
+    // Black has to pass, then White ends the game
+    let mut board = Board::from_position(&format!("OX{} X", "-".repeat(62))).unwrap();
+    assert_eq!(board.play_move(&(0, 2)), Err(OthelloError::MustPass));
+    assert_eq!(board.move_with_highest_gain(), Err(OthelloError::MustPass));
+    board.pass().unwrap();
+    board.play_move(&(0, 2)).unwrap();
+    assert_eq!(board.play_move(&(0, 3)), Err(OthelloError::GameOver));
+    assert_eq!(board.pass(), Err(OthelloError::GameOver));
+
+    let weights = EvaluatorKind::Pattern("/nonexistent/weights.bin".into()).build();
+    assert!(matches!(weights, Err(OthelloError::Weights(_))));
+    let mut manual = Player::new((Strategy::Manual, Strategy::Random));
+    assert_eq!(
+        manual.play_games(1, false, 1),
+        Err(OthelloError::ManualInAutomatic)
+    );
+    let error: Box<dyn std::error::Error> = Box::new(OthelloError::Occupied((4, 5)));
+    assert_eq!(error.to_string(), "Illegal move f5: the square is taken");
+}
@@ -0,0 +1,229 @@
+use crate::board::{Board, Case};
+use crate::error::OthelloError;
+use crate::pattern::PatternEvaluator;
+use crate::rules::square_coords;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Static evaluation of the positions at the horizon of the search. The symmetric
+/// images of a position must get the same value, the searcher and the training
+/// only seeing one of them.
+pub trait Evaluator: Send + Sync {
+    /// Value of the position for `color`, the higher the better
+    fn evaluate(&self, board: &Board, color: Case) -> i32;
+}
+
+/// Evaluators the minimax players can be given
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum EvaluatorKind {
+    /// Disc balance, weighted more and more as the game goes, and placement table
+    #[default]
+    Placement,
+    /// Mobility, frontier, corners and stability, weighted by game phase
+    Features,
+    /// Weights of the configurations of edge, corner and diagonal patterns, read from a file
+    Pattern(PathBuf),
+}
+
+impl EvaluatorKind {
+    /// Create the evaluator, reading its weights if it has some
+    /// # Returns
+    /// * `Err(OthelloError::Weights)` if they cannot be read
+    pub fn build(&self) -> Result<Arc<dyn Evaluator>, OthelloError> {
+        Ok(match self {
+            EvaluatorKind::Placement => Arc::new(PlacementEvaluator),
+            EvaluatorKind::Features => Arc::new(FeatureEvaluator),
+            EvaluatorKind::Pattern(path) => Arc::new(
+                PatternEvaluator::load(path)
+                    .map_err(|e| OthelloError::Weights(format!("{}: {}", path.display(), e)))?,
+            ),
+        })
+    }
+}
+
+pub const PLACEMENT_SCORE: [[isize; 8]; 8] = [
+    [256, -8, 16, 16, 16, 16, -8, 256],
+    [-8, -8, -4, -4, -4, -4, -8, -8],
+    [16, -4, 0, 0, 0, 0, -4, 16],
+    [16, -4, 0, 0, 0, 0, -4, 16],
+    [16, -4, 0, 0, 0, 0, -4, 16],
+    [16, -4, 0, 0, 0, 0, -4, 16],
+    [-8, -8, -4, -4, -4, -4, -8, -8],
+    [256, -8, 16, 16, 16, 16, -8, 256],
+];
+
+/// (white,black)
+pub fn matrix_eval(board: &Board) -> (isize, isize) {
+    let sum = |mut discs: u64| {
+        let mut res = 0;
+        while discs != 0 {
+            let (i, j) = square_coords(discs.trailing_zeros());
+            res += PLACEMENT_SCORE[i][j];
+            discs &= discs - 1;
+        }
+        res
+    };
+    (sum(board.discs(Case::White)), sum(board.discs(Case::Black)))
+}
+
+pub struct PlacementEvaluator;
+
+impl Evaluator for PlacementEvaluator {
+    fn evaluate(&self, board: &Board, color: Case) -> i32 {
+        let score = board.score();
+        let filled = score.0 + score.1;
+        let balance = if color == Case::Black {
+            score.0 as i32 - score.1 as i32
+        } else {
+            score.1 as i32 - score.0 as i32
+        };
+        if board.is_ended() {
+            final_value(balance)
+        } else {
+            let state = filled / 16; // Découpe la partie en plusieurs phases
+            let mut result = balance << state; // On attribue une importance grandissante au score en fonction de l'avancement de la partie
+            let matrix = matrix_eval(board);
+            result += if color == Case::Black {
+                matrix.1 as i32 - matrix.0 as i32 // Différence de score de placement
+            } else {
+                matrix.0 as i32 - matrix.1 as i32
+            };
+            result
+        }
+    }
+}
+
+/// Value added to a won game, above any evaluation of an unfinished one
+const WIN_BONUS: i32 = 100_000;
+
+/// Value of a finished game: the disc differential in hundredths of a disc,
+/// the scale of the pattern evaluator, and the bonus of the winner
+pub fn final_value(balance: i32) -> i32 {
+    WIN_BONUS * balance.signum() + 100 * balance
+}
+
+/// Weights of the features of `FeatureEvaluator`, applied to the difference
+/// between the player and its opponent
+struct FeatureWeights {
+    discs: i32,
+    mobility: i32,
+    potential_mobility: i32,
+    frontier: i32,
+    corners: i32,
+    corner_neighbours: i32,
+    stable: i32,
+}
+
+/// Weights at the start of the game: discs are a liability, moves and corners matter
+const OPENING_WEIGHTS: FeatureWeights = FeatureWeights {
+    discs: -1,
+    mobility: 8,
+    potential_mobility: 4,
+    frontier: -4,
+    corners: 80,
+    corner_neighbours: -25,
+    stable: 10,
+};
+
+/// Weights near the end of the game, where the discs count
+const ENDING_WEIGHTS: FeatureWeights = FeatureWeights {
+    discs: 6,
+    mobility: 4,
+    potential_mobility: 1,
+    frontier: -1,
+    corners: 40,
+    corner_neighbours: -10,
+    stable: 12,
+};
+
+pub struct FeatureEvaluator;
+
+impl Evaluator for FeatureEvaluator {
+    fn evaluate(&self, board: &Board, color: Case) -> i32 {
+        let opponent = color.opponent();
+        let diff = |feature: &dyn Fn(Case) -> u32| feature(color) as i32 - feature(opponent) as i32;
+        let discs = diff(&|c| board.discs(c).count_ones());
+        if board.is_ended() {
+            return final_value(discs);
+        }
+        let features = [
+            discs,
+            diff(&|c| board.mobility(c)),
+            diff(&|c| board.potential_mobility(c)),
+            diff(&|c| board.frontier(c).count_ones()),
+            diff(&|c| board.corners(c)),
+            diff(&|c| board.corner_neighbours(c).count_ones()),
+            diff(&|c| board.stable_discs(c).count_ones()),
+        ];
+        let weighted = |w: &FeatureWeights| {
+            let weights = [
+                w.discs,
+                w.mobility,
+                w.potential_mobility,
+                w.frontier,
+                w.corners,
+                w.corner_neighbours,
+                w.stable,
+            ];
+            features
+                .iter()
+                .zip(weights)
+                .map(|(f, w)| f * w)
+                .sum::<i32>()
+        };
+        // Passage progressif des poids d'ouverture à ceux de fin de partie
+        let empties = board.empties() as i32;
+        (weighted(&OPENING_WEIGHTS) * empties + weighted(&ENDING_WEIGHTS) * (60 - empties)) / 60
+    }
+}
+
+#[test]
+fn placement_evaluator_test() {
+    let evaluator = EvaluatorKind::Placement.build().unwrap();
+    let mut board = Board::new();
+    assert_eq!(evaluator.evaluate(&board, Case::Black), 0);
+    board.play_move(&(2, 3)).expect("Move should be legal");
+    // Black has three discs more, all in the center
+    assert_eq!(evaluator.evaluate(&board, Case::Black), 3);
+    for m in [(2, 2), (3, 2), (2, 4)] {
+        board.play_move(&m).expect("Move should be legal");
+        let black = evaluator.evaluate(&board, Case::Black);
+        assert_eq!(black, -evaluator.evaluate(&board, Case::White));
+    }
+}
+
+#[test]
+fn feature_evaluator_test() {
+    let evaluator = EvaluatorKind::Features.build().unwrap();
+    let mut board = Board::new();
+    assert_eq!(evaluator.evaluate(&board, Case::Black), 0);
+    while !board.is_ended() {
+        let black = evaluator.evaluate(&board, Case::Black);
+        assert_eq!(black, -evaluator.evaluate(&board, Case::White));
+        let moves = board.available_moves(None);
+        let m = (!moves.is_empty()).then(|| moves[moves.len() / 2]);
+        board.play(m).expect("Move should be legal");
+    }
+    let (black, white) = board.score();
+    let value = evaluator.evaluate(&board, Case::Black);
+    assert_eq!(value.signum(), (black as i32 - white as i32).signum());
+}
+
+#[test]
+fn symmetric_evaluators_test() {
+    use crate::board::Symmetry;
+    for kind in [EvaluatorKind::Placement, EvaluatorKind::Features] {
+        let evaluator = kind.build().unwrap();
+        let mut board = Board::new();
+        while !board.is_ended() {
+            let color = board.get_turn();
+            let value = evaluator.evaluate(&board, color);
+            for symmetry in Symmetry::all() {
+                let image = board.transform(symmetry);
+                assert_eq!(evaluator.evaluate(&image, color), value, "{:?}", kind);
+            }
+            let moves = board.available_moves(None);
+            board.play(moves.get(moves.len() / 3).copied()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::board::{Board, BoardState, Case, Move};
+use crate::endgame::final_score;
+use crate::error::{LoadError, OthelloError};
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Evaluation and thinking time written with a move, `B[F5/1.50/2.03]`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Annotation {
+    /// Evaluation of the player of the move, in discs
+    pub eval: Option<f32>,
+    /// Time spent on the move, in seconds
+    pub time: Option<f32>,
+}
+
+/// Game record in the Generic Game Format of the online servers:
+/// `(;GM[Othello]PB[...]PW[...]RE[+12.000]BO[8 ... *]B[F5//1.2]W[D6]...;)`
+#[derive(Clone, Debug)]
+pub struct GgfGame {
+    pub black: String,
+    pub white: String,
+    /// Time control as written in the record
+    pub time_control: Option<String>,
+    /// Final disc differential for Black, the empty squares going to the winner
+    pub result: Option<f32>,
+    /// Position the game starts from
+    start: Board,
+    /// Position at the end of the record, the moves from `start` being in its history,
+    /// which are checked when the record is created
+    board: Board,
+    /// Annotation of each move of the history
+    pub annotations: Vec<Annotation>,
+}
+
+impl GgfGame {
+    /// Record of a game, without annotations
+    /// # Arguments
+    /// * `start` - The position the game starts from
+    /// * `moves` - The moves played from it, `None` being a pass
+    /// # Returns
+    /// * `Err(OthelloError)` if one of the moves is illegal
+    pub fn new(
+        black: &str,
+        white: &str,
+        start: &Board,
+        moves: &[Option<(usize, usize)>],
+    ) -> Result<Self, OthelloError> {
+        let mut board = start.clone();
+        for &bmove in moves {
+            board.play(bmove)?;
+        }
+        let result = final_score(board.discs(Case::Black), board.discs(Case::White));
+        Ok(GgfGame {
+            black: black.to_string(),
+            white: white.to_string(),
+            time_control: None,
+            result: board.is_ended().then_some(result as f32),
+            start: start.clone(),
+            annotations: vec![Annotation::default(); moves.len()],
+            board,
+        })
+    }
+
+    /// Position the game starts from
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    /// Position at the end of the record
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Read one game record
+    /// # Returns
+    /// * `Err(OthelloError)` if the record is malformed, or one of its moves illegal
+    pub fn parse(record: &str) -> Result<GgfGame, OthelloError> {
+        let invalid =
+            |message: String| OthelloError::Parse(format!("Invalid GGF record: {}", message));
+        let body = record
+            .trim()
+            .strip_prefix("(;")
+            .and_then(|body| body.strip_suffix(";)"))
+            .ok_or_else(|| invalid("it should be written between (; and ;)".to_string()))?;
+        let mut game = GgfGame {
+            black: String::new(),
+            white: String::new(),
+            time_control: None,
+            result: None,
+            start: Board::new(),
+            board: Board::new(),
+            annotations: Vec::new(),
+        };
+        let mut rest = body;
+        while let Some(open) = rest.find('[') {
+            let close = (rest[open..].find(']'))
+                .ok_or_else(|| invalid("unclosed property".to_string()))?
+                + open;
+            let (key, value) = (rest[..open].trim(), &rest[open + 1..close]);
+            rest = &rest[close + 1..];
+            match key {
+                "GM" if !value.eq_ignore_ascii_case("othello") => {
+                    return Err(invalid(format!("not an Othello game ({})", value)))
+                }
+                "PB" => game.black = value.to_string(),
+                "PW" => game.white = value.to_string(),
+                "TI" => game.time_control = Some(value.to_string()),
+                "RE" => game.result = value.split(':').next().and_then(|r| r.parse().ok()),
+                "BO" => {
+                    let position = (value.strip_prefix("8 "))
+                        .ok_or_else(|| invalid(format!("unsupported board {}", value)))?;
+                    game.start = Board::from_position(position)?;
+                    game.board = game.start.clone();
+                    game.annotations.clear();
+                }
+                "B" | "W" => game.play(key, value)?,
+                // Date, lieu, type de partie...
+                _ => {}
+            }
+        }
+        Ok(game)
+    }
+
+    /// Play a move property of the record, `value` being `move/eval/time`
+    fn play(&mut self, color: &str, value: &str) -> Result<(), OthelloError> {
+        let mut fields = value.split('/');
+        let bmove: Move = fields.next().unwrap_or("").trim().parse()?;
+        let annotation = Annotation {
+            eval: fields.next().and_then(|e| e.trim().parse().ok()),
+            time: fields.next().and_then(parse_time),
+        };
+        let expected = if color == "B" {
+            Case::Black
+        } else {
+            Case::White
+        };
+        // Passe implicite : l'adversaire rejoue
+        if bmove.0.is_some()
+            && self.board.get_turn() != expected
+            && self.board.get_state() == BoardState::MustPass
+        {
+            self.board.pass()?;
+            self.annotations.push(Annotation::default());
+        }
+        if self.board.get_turn() != expected {
+            return Err(OthelloError::Parse(format!(
+                "Invalid GGF record: {} is not to move",
+                expected
+            )));
+        }
+        self.board.play(bmove.0)?;
+        self.annotations.push(annotation);
+        Ok(())
+    }
+}
+
+/// Read a time written in seconds, or as minutes and seconds `m:ss`
+fn parse_time(time: &str) -> Option<f32> {
+    time.trim().split(':').try_fold(0., |total, part| {
+        Some(total * 60. + part.parse::<f32>().ok()?)
+    })
+}
+
+impl Display for GgfGame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Les crochets fermeraient la propriété
+        let clean = |s: &str| s.replace(']', ")");
+        write!(
+            f,
+            "(;GM[Othello]PB[{}]PW[{}]",
+            clean(&self.black),
+            clean(&self.white)
+        )?;
+        if let Some(result) = self.result {
+            write!(f, "RE[{:+.3}]", result)?;
+        }
+        if let Some(time_control) = &self.time_control {
+            write!(f, "TI[{}]", clean(time_control))?;
+        }
+        write!(f, "TY[8]BO[8 {}]", self.start.position().replace('X', "*"))?;
+        // Les passes étant dans l'historique, les couleurs alternent
+        let mut color = self.start.get_turn();
+        for (i, &bmove) in self.board.history.moves.iter().enumerate() {
+            let player = if color == Case::Black { "B" } else { "W" };
+            write!(f, "{}[{}", player, Move(bmove).to_string().to_uppercase())?;
+            let annotation = self.annotations.get(i).copied().unwrap_or_default();
+            if annotation != Annotation::default() {
+                let eval = (annotation.eval).map_or(String::new(), |e| format!("{:.2}", e));
+                let time = (annotation.time).map_or(String::new(), |t| format!("{:.2}", t));
+                write!(f, "/{}/{}", eval, time)?;
+            }
+            write!(f, "]")?;
+            color = color.opponent();
+        }
+        write!(f, ";)")
+    }
+}
+
+/// Read every game record of a file
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<GgfGame>, LoadError> {
+    let text = fs::read_to_string(path)?;
+    let games = text
+        .split_inclusive(";)")
+        .filter(|record| !record.trim().is_empty())
+        .map(GgfGame::parse)
+        .collect::<Result<_, _>>()?;
+    Ok(games)
+}
+
+/// Write the games, one record per line
+pub fn save(path: impl AsRef<Path>, games: &[GgfGame]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for game in games {
+        writeln!(file, "{}", game)?;
+    }
+    file.flush()
+}
+
+#[test]
+fn ggf_test() {
+    let record = "(;GM[Othello]PC[NIOS]DT[2003.12.15_13:24:03.MST]PB[alice]PW[bob]\
+        RE[-4.000:r]TI[15:00//02:00]TY[8]\
+        BO[8 ---------------------------O*------*O--------------------------- *]\
+        B[f5//0.01]W[D6/-1.50/1:02]B[c3];)";
+    let game = GgfGame::parse(record).unwrap();
+    assert_eq!((game.black.as_str(), game.white.as_str()), ("alice", "bob"));
+    assert_eq!(game.result, Some(-4.));
+    assert_eq!(game.time_control.as_deref(), Some("15:00//02:00"));
+    assert_eq!(game.start, Board::new());
+    assert_eq!(game.board.transcript(), "f5d6c3");
+    assert_eq!(game.annotations[0].time, Some(0.01));
+    assert_eq!(
+        game.annotations[1],
+        Annotation {
+            eval: Some(-1.5),
+            time: Some(62.)
+        }
+    );
+
+    // Written then read back
+    let written = game.to_string();
+    let read = GgfGame::parse(&written).unwrap();
+    assert_eq!(read.board.history.moves, game.board.history.moves);
+    assert_eq!(read.annotations, game.annotations);
+    assert_eq!(read.result, game.result);
+    assert_eq!(read.to_string(), written);
+
+    // Game from another position, where Black has to pass at once
+    let position = format!("OX{} *", "-".repeat(62));
+    let board = Board::from_position(&position).unwrap();
+    let record = GgfGame::new("a", "b", &board, &[None, Some((0, 2))]).unwrap();
+    assert!(record.to_string().contains("B[PA]W[C1]"));
+    // White wins with 3 discs, and the empty squares
+    assert_eq!(record.result, Some(-64.));
+    assert!(GgfGame::new("a", "b", &board, &[Some((0, 2))]).is_err());
+    let read = GgfGame::parse(&record.to_string()).unwrap();
+    assert_eq!(read.board.history.moves, vec![None, Some((0, 2))]);
+    // The pass may be left out
+    let read = GgfGame::parse(&format!("(;BO[8 {}]W[C1];)", position)).unwrap();
+    assert_eq!(read.board.history.moves, vec![None, Some((0, 2))]);
+    assert!(read.board.is_ended());
+
+    let path = std::env::temp_dir().join("othello_ggf_test.ggf");
+    save(&path, &[game.clone(), read]).unwrap();
+    let games = load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].board, game.board);
+
+    for invalid in [
+        "",
+        "(;GM[Chess]B[f5];)",
+        "(;GM[Othello]B[f5]",
+        "(;B[e5];)",
+        "(;W[f5];)",
+        "(;B[f5;)",
+        "(;BO[10 -];)",
+    ] {
+        assert!(GgfGame::parse(invalid).is_err(), "{}", invalid);
+    }
+}
@@ -1,17 +1,248 @@
 use std::env;
+use std::io;
 
+mod bench;
 mod board;
+mod book;
+mod endgame;
+mod error;
+mod evaluation;
+mod ggf;
 mod mcts;
 mod minimax;
+mod ordering;
+mod pattern;
 mod player;
 mod rules;
+mod train;
+mod transposition;
+mod wthor;
+
+/// Number of threads of the program: of the parallel games, or of a single search
+const THREADS: usize = 4;
+
+/// Games of a WTHOR file, when the argument is not a number of games
+fn wthor_games(arg: Option<&String>) -> Option<Result<Vec<wthor::WthorGame>, error::LoadError>> {
+    let path = arg.filter(|a| a.parse::<usize>().is_err())?;
+    Some(wthor::load(path))
+}
 
 fn main() {
-    let _rayon_pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(4)
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(THREADS)
         .build_global()
         .unwrap();
     let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        // othello bench [depth] [max threads]
+        let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(10);
+        let threads = args.get(3).and_then(|t| t.parse().ok()).unwrap_or(THREADS);
+        if let Err(e) = bench::smp_speedup(depth, threads) {
+            eprintln!("Cannot run the benchmark: {}", e);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("book") {
+        // othello book [games | WTHOR file] [book file] [depth] [plies]
+        let path = args.get(3).map_or("book.txt", String::as_str);
+        let depth = args.get(4).and_then(|d| d.parse().ok()).unwrap_or(6);
+        let plies = args.get(5).and_then(|p| p.parse().ok()).unwrap_or(12);
+        // On complète le livre existant, avec des parties de la base ou jouées par le programme
+        let mut book = match book::Book::load(path) {
+            Ok(book) => book,
+            Err(error::LoadError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                book::Book::default()
+            }
+            Err(e) => {
+                eprintln!("Cannot read the book {}: {}", path, e);
+                return;
+            }
+        };
+        match wthor_games(args.get(2)) {
+            Some(Ok(games)) => {
+                for (i, game) in games.iter().enumerate() {
+                    if let Err(e) = book.add_game(&game.moves, plies) {
+                        eprintln!("Game {} of {} left out: {}", i + 1, args[2], e);
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                eprintln!("Cannot read the games {}: {}", args[2], e);
+                return;
+            }
+            None => {
+                let games = args.get(2).map_or(1000, |g| g.parse().unwrap());
+                // Les parties déjà ajoutées sont gardées
+                if let Err(e) = book::build(&mut book, games, depth, plies) {
+                    eprintln!("Cannot play the games: {}", e);
+                }
+            }
+        }
+        println!("Book: {} positions", book.len());
+        if let Err(e) = book.save(path) {
+            eprintln!("Cannot write the book {}: {}", path, e);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("perft") {
+        // othello perft [depth] ["<position>"]
+        let depth = args.get(2).and_then(|d| d.parse().ok()).unwrap_or(9);
+        let mut board = match args.get(3).map(|p| board::Board::from_position(p)) {
+            Some(Ok(board)) => board,
+            Some(Err(e)) => {
+                eprintln!("Cannot read the position: {}", e);
+                return;
+            }
+            None => board::Board::new(),
+        };
+        for depth in 1..=depth {
+            let start = std::time::Instant::now();
+            let count = board.perft(depth);
+            println!("Depth {}: {} ({:.2?})", depth, count, start.elapsed());
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("stats") {
+        // othello stats <WTHOR file>
+        let path = args.get(2).map_or("", String::as_str);
+        let games = match wthor::load(path) {
+            Ok(games) => games,
+            Err(e) => {
+                eprintln!("Cannot read the games {}: {}", path, e);
+                return;
+            }
+        };
+        let n = games.len().max(1) as f64;
+        let count = |won: fn(i32) -> bool| games.iter().filter(|g| won(g.score())).count();
+        println!("{} games", games.len());
+        println!(
+            "Black: {:.1} %, White: {:.1} %, Draw: {:.1} %",
+            count(|score| score > 0) as f64 / n * 100.,
+            count(|score| score < 0) as f64 / n * 100.,
+            count(|score| score == 0) as f64 / n * 100.
+        );
+        let average = games.iter().map(|g| g.score() as f64).sum::<f64>() / n;
+        println!("Average score for Black: {:+.2}", average);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("train") {
+        // othello train [games | WTHOR file] [weights file] [epochs]
+        let games = match wthor_games(args.get(2)) {
+            Some(Ok(games)) => games.into_iter().map(|game| game.moves).collect(),
+            Some(Err(e)) => {
+                eprintln!("Cannot read the games {}: {}", args[2], e);
+                return;
+            }
+            None => {
+                let games = args.get(2).map_or(1000, |g| g.parse().unwrap());
+                println!("Playing {} games...", games);
+                match train::self_play(games, &mut rand::thread_rng()) {
+                    Ok(games) => games,
+                    Err(e) => {
+                        eprintln!("Cannot play the games: {}", e);
+                        return;
+                    }
+                }
+            }
+        };
+        let path = args.get(3).map_or("weights.bin", String::as_str);
+        let epochs = args.get(4).and_then(|e| e.parse().ok()).unwrap_or(50);
+        if let Err(e) = train::train(&games, epochs, path) {
+            eprintln!("Cannot write the weights {}: {}", path, e);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("show") {
+        // othello show <transcript>, e.g. f5d6c3d3c4
+        let transcript = args.get(2).map_or("", String::as_str);
+        match board::Board::from_transcript(transcript) {
+            Ok(board) => {
+                println!("{}", board);
+                let (black, white) = board.score();
+                println!("Black: {}, White: {}", black, white);
+                println!("To move: {}", board.get_turn());
+                println!("Position: {}", board.position());
+            }
+            Err(e) => eprintln!("Cannot replay the game: {}", e),
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("analyze") {
+        // othello analyze "<position>" [depth] [weights file], e.g. "--XXXXX--OOOXX-O... X"
+        let position = args.get(2).map_or("", String::as_str);
+        let board = match board::Board::from_position(position) {
+            Ok(board) => board,
+            Err(e) => {
+                eprintln!("Cannot read the position: {}", e);
+                return;
+            }
+        };
+        let depth = args.get(3).and_then(|d| d.parse().ok()).unwrap_or(10);
+        let evaluator = match args.get(4) {
+            Some(weights) => evaluation::EvaluatorKind::Pattern(weights.into()),
+            None => evaluation::EvaluatorKind::Features,
+        };
+        let strategy = player::Strategy::Minimax {
+            depth,
+            evaluator,
+            solve_empties: endgame::DEFAULT_SOLVE_EMPTIES,
+            threads: THREADS,
+        };
+        println!("{}", board);
+        match player::Player::new((strategy.clone(), strategy)).analyze(&board) {
+            Ok(Some(bmove)) => {
+                println!("{} plays {}", board.get_turn(), board::Move(Some(bmove)))
+            }
+            Ok(None) if board.is_ended() => println!("The game is over"),
+            Ok(None) => println!("{} has to pass", board.get_turn()),
+            Err(e) => eprintln!("Cannot analyze the position: {}", e),
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("replay") {
+        // othello replay <GGF file> [depth] [weights file]
+        let path = args.get(2).map_or("games.ggf", String::as_str);
+        let games = match ggf::load(path) {
+            Ok(games) => games,
+            Err(e) => {
+                eprintln!("Cannot read the games {}: {}", path, e);
+                return;
+            }
+        };
+        let depth = args.get(3).and_then(|d| d.parse().ok()).unwrap_or(6);
+        let evaluator = match args.get(4) {
+            Some(weights) => evaluation::EvaluatorKind::Pattern(weights.into()),
+            None => evaluation::EvaluatorKind::Features,
+        };
+        let strategy = player::Strategy::Minimax {
+            depth,
+            evaluator,
+            solve_empties: endgame::DEFAULT_SOLVE_EMPTIES,
+            threads: THREADS,
+        };
+        let player = player::Player::new((strategy.clone(), strategy));
+        let (mut found, mut asked) = (0, 0);
+        for game in &games {
+            let (f, a) = match player.replay(game.start(), &game.board().history.moves) {
+                Ok(found) => found,
+                Err(e) => {
+                    eprintln!("Cannot replay the game: {}", e);
+                    return;
+                }
+            };
+            println!(
+                "{} - {} ({}): {}/{} moves found",
+                game.black,
+                game.white,
+                game.result.map_or("?".to_string(), |r| format!("{:+}", r)),
+                f,
+                a
+            );
+            (found, asked) = (found + f, asked + a);
+        }
+        println!("Total: {}/{} moves found", found, asked);
+        return;
+    }
     let budget1 = &args[1].parse::<usize>().unwrap();
     let budget2 = &args[2].parse::<u8>().unwrap();
     let games = &args
@@ -19,13 +250,38 @@ fn main() {
         .unwrap_or(&String::from("100"))
         .parse::<u32>()
         .unwrap_or(100);
+    // Poids des motifs du joueur minimax, livre d'ouvertures et archive GGF des parties ("-" si absents)
+    let evaluator = match args.get(4).filter(|weights| *weights != "-") {
+        Some(weights) => evaluation::EvaluatorKind::Pattern(weights.into()),
+        None => evaluation::EvaluatorKind::Features,
+    };
     let mut player = player::Player::new((
-        player::Strategy::Minimax { depth: *budget2 },
+        player::Strategy::Minimax {
+            depth: *budget2,
+            evaluator,
+            solve_empties: endgame::DEFAULT_SOLVE_EMPTIES,
+            // Les parties se jouent déjà en parallèle
+            threads: 1,
+        },
         player::Strategy::MCTS {
             playout_budget: *budget1,
             final_solve: true,
+            solve_empties: endgame::DEFAULT_SOLVE_EMPTIES,
+            wld_empties: endgame::DEFAULT_WLD_EMPTIES,
         },
     ));
+    if let Some(path) = args.get(5).filter(|book| *book != "-") {
+        match book::Book::load(path) {
+            Ok(book) => player = player.with_book(book, book::BookChoice::Weighted),
+            Err(e) => eprintln!("Cannot read the book {}: {}", path, e),
+        }
+    }
+    // Fichier GGF où archiver les parties
+    if let Some(path) = args.get(6) {
+        player = player.with_archive(path);
+    }
     println!("exploration constant: {}", mcts::EXPLORATION_PARAMETER);
-    player.play_games(*games, true, 50);
+    if let Err(e) = player.play_games(*games, true, 50) {
+        eprintln!("Cannot play the games: {}", e);
+    }
 }
@@ -1,9 +1,10 @@
-#![allow(non_snake_case)]
+#![allow(non_snake_case, clippy::upper_case_acronyms)]
 
-use core::panic;
 use std::collections::HashMap;
 
 use crate::board::{Board, BoardState, Case, EndState};
+use crate::endgame::{EndgameSolver, Outcome};
+use crate::error::OthelloError;
 use fxhash::FxHashMap;
 use rand::{seq::SliceRandom, thread_rng};
 
@@ -22,19 +23,21 @@ struct Node {
 }
 
 impl Node {
-    fn from_expansion(parent: &Node, move_: (usize, usize)) -> (Node, EndState) {
+    fn from_expansion(
+        parent: &Node,
+        move_: (usize, usize),
+    ) -> Result<(Node, EndState), OthelloError> {
         let mut board = parent.state.clone();
-        let player = parent.turn.opponent();
-        let (is_end_state, endstate) = match board.play_move(&move_) {
-            Ok(BoardState::Ongoing) => (
-                false,
-                Node::simulate_random_playout(&mut board.clone(), player),
-            ),
-            Ok(BoardState::Ended(endstate)) => (true, endstate),
-            Err(msg) => panic!(
-                "error in Node.from_expansion when calling board.play_move(): {}",
-                msg
-            ),
+        let player = parent.state.get_turn();
+        // Forced passes are played right away so that every node has a player able to move
+        let state = match board.play_move(&move_)? {
+            BoardState::MustPass => board.pass()?,
+            state => state,
+        };
+        let (is_end_state, endstate) = match state {
+            BoardState::Ongoing => (false, Node::simulate_random_playout(&mut board.clone())?),
+            BoardState::MustPass => unreachable!(),
+            BoardState::Ended(endstate) => (true, endstate),
         };
         let mut node = Node {
             state: board,
@@ -49,23 +52,19 @@ impl Node {
 
         node.update_from_endstate(endstate);
 
-        (node, endstate)
+        Ok((node, endstate))
     }
 
-    fn simulate_random_playout(board: &mut Board, player: Case) -> EndState {
-        let mut curr_player = player;
+    fn simulate_random_playout(board: &mut Board) -> Result<EndState, OthelloError> {
+        let mut rng = thread_rng();
         loop {
-            let mut rng = thread_rng();
-            let game_state =
-                board.play_move(&board.available_moves(None).choose(&mut rng).unwrap());
-            curr_player = curr_player.opponent();
-            match game_state {
-                Ok(state) => match state {
-                    BoardState::Ongoing => (),
-                    BoardState::Ended(endstate) => return endstate,
-                },
-                Err(msg) => panic!("Err in Node.simulate_random_playout(): {}", msg),
-            }
+            // The playout is not recorded in the history of the board
+            let bmove = match board.get_state() {
+                BoardState::Ongoing => board.available_moves(None).choose(&mut rng).copied(),
+                BoardState::MustPass => None,
+                BoardState::Ended(endstate) => return Ok(endstate),
+            };
+            board.make_move(bmove)?;
         }
     }
 
@@ -110,19 +109,19 @@ impl Node {
     }
 
     //Un round d'expansion
-    fn expand(&mut self) -> EndState {
+    fn expand(&mut self) -> Result<EndState, OthelloError> {
         if self.is_fully_expanded {
-            return EndState::Winner(Case::Empty);
+            return Ok(EndState::Winner(Case::Empty));
         }
 
         let mut moves = self.state.available_moves(None);
         for move_ in moves.iter() {
             if !self.children.contains_key(move_) {
-                let (child_node, endstate) = Node::from_expansion(self, *move_);
+                let (child_node, endstate) = Node::from_expansion(self, *move_)?;
                 self.children.insert(*move_, child_node); // Ajoute le move aux children
                 self.update_from_endstate(endstate); // Si le move est terminal on update les scores
                 self.update_fully_expanded(); // Si tous les children sont fully expanded on le note comme fully expanded
-                return endstate;
+                return Ok(endstate);
             }
         }
         // Si on arrive là, tous les children ont déjà été visités
@@ -138,14 +137,16 @@ impl Node {
         while let Some(best_move) = moves.pop() {
             let child = self.children.get_mut(&best_move).unwrap();
             if !child.is_fully_expanded {
-                let endstate = child.expand(); //On expand le meilleur move qui n'est pas encore fully expanded
+                let endstate = child.expand()?; //On expand le meilleur move qui n'est pas encore fully expanded
                 self.update_from_endstate(endstate);
                 self.update_fully_expanded();
-                return endstate;
+                return Ok(endstate);
             }
         }
 
-        panic!("Expand is broken !")
+        Err(OthelloError::Engine(
+            "every child was expanded, but not the node".to_string(),
+        ))
     }
 
     pub fn update_winning_state(turn: Case, current: Case, to_add: Case) -> Case {
@@ -158,19 +159,16 @@ impl Node {
         current
     }
 
-    pub fn generate_winning_state(&mut self) -> () {
+    pub fn generate_winning_state(&mut self) {
         // Algorithme de recherche d'attracteur
         if self.state.is_ended() {
             self.winning_state = Some(self.state.current_winner());
         } else {
-            let mut wstate = self.turn;
+            let to_move = self.state.get_turn();
+            let mut wstate = to_move.opponent();
             for node in self.children.values_mut() {
                 node.generate_winning_state();
-                wstate = Node::update_winning_state(
-                    self.turn.opponent(),
-                    wstate,
-                    node.winning_state.unwrap(),
-                );
+                wstate = Node::update_winning_state(to_move, wstate, node.winning_state.unwrap());
             }
             self.winning_state = Some(wstate);
         }
@@ -215,11 +213,29 @@ pub struct MCTS {
     root: Node,
     playout_budget: usize,
     final_solve: bool,
+    /// Number of empty squares from which the search proves a win or a draw
+    /// instead of running playouts, 0 to never do so
+    wld_empties: u8,
 }
 
 impl MCTS {
-    pub fn new(player: Case, final_solve: bool, playout_budget: usize, board: Board) -> MCTS {
-        let root = Node {
+    pub fn new(
+        player: Case,
+        final_solve: bool,
+        playout_budget: usize,
+        wld_empties: u8,
+        board: Board,
+    ) -> MCTS {
+        MCTS {
+            playout_budget,
+            root: MCTS::new_root(player, board),
+            final_solve,
+            wld_empties,
+        }
+    }
+
+    fn new_root(player: Case, board: Board) -> Node {
+        Node {
             state: board,
             turn: player.opponent(),
             is_fully_expanded: false,
@@ -228,18 +244,18 @@ impl MCTS {
             children: HashMap::default(),
             exploration_constant: EXPLORATION_PARAMETER,
             winning_state: None,
-        };
-
-        MCTS {
-            playout_budget,
-            root,
-            final_solve,
         }
     }
 
-    pub fn search(&mut self, board: &Board) -> (usize, usize) {
-        if let Some(opp_move) = self.get_opponents_last_move(board) {
-            self.update_with_opponents_move(opp_move, board);
+    /// Find the move to play, the tree following the game since the last search
+    /// # Returns
+    /// * `Err(OthelloError)` if the player to move cannot move
+    pub fn search(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        board.can_move()?;
+        self.update_with_opponents_moves(board);
+        if let Some(move_) = self.proven_move(board) {
+            // Le noeud du coup n'existe pas forcément : la prochaine recherche s'en charge
+            return Ok(move_);
         }
         let move_ = {
             if self.root.is_fully_expanded && self.final_solve {
@@ -252,31 +268,52 @@ impl MCTS {
 
                 for (m, n) in &self.root.children {
                     if n.winning_state.unwrap() == self.root.winning_state.unwrap() {
-                        moves.push((m.clone(), n));
+                        moves.push((*m, n));
                     }
                 }
                 let (m, _) = *moves.choose(&mut rng).unwrap();
                 m
             } else {
                 for _ in 0..self.playout_budget {
-                    self.root.expand();
+                    self.root.expand()?;
                 }
 
                 self.get_best_move()
             }
         };
         self.promote_child(&move_);
-        move_
+        Ok(move_)
     }
 
-    fn get_opponents_last_move(&self, board: &Board) -> Option<(usize, usize)> {
-        board.history.moves.last().copied()
+    /// Solve the game once few enough squares are left, keeping the playouts
+    /// for lost positions where the opponent may still go wrong
+    /// # Returns
+    /// * A move securing a win or a draw, if there is one
+    fn proven_move(&self, board: &Board) -> Option<(usize, usize)> {
+        if board.empties() > self.wld_empties as u32 {
+            return None;
+        }
+        match EndgameSolver::new().solve_wld(board) {
+            (Outcome::Win | Outcome::Draw, move_) => move_,
+            (Outcome::Loss, _) => None,
+        }
     }
 
-    fn update_with_opponents_move(&mut self, opp_move: (usize, usize), board: &Board) {
-        match self.root.children.remove(&opp_move) {
-            Some(child_node) => self.root = child_node,
-            None => self.root.state = board.clone(),
+    /// Follow the moves played since our last search down the tree,
+    /// starting over from `board` when they were not explored
+    fn update_with_opponents_moves(&mut self, board: &Board) {
+        let mut played = self.root.state.history.moves.len();
+        while played < board.history.moves.len() {
+            let child_node = board.history.moves[played]
+                .and_then(|opp_move| self.root.children.remove(&opp_move));
+            match child_node {
+                Some(child_node) => self.root = child_node,
+                None => {
+                    self.root = MCTS::new_root(board.get_turn(), board.clone());
+                    return;
+                }
+            }
+            played = self.root.state.history.moves.len();
         }
     }
 
@@ -299,7 +336,7 @@ impl MCTS {
         best_move
     }
 
-    fn promote_child(&mut self, move_: &(usize, usize)) -> () {
+    fn promote_child(&mut self, move_: &(usize, usize)) {
         let new_root = self.root.children.remove(move_).unwrap();
         self.root = new_root;
     }
@@ -332,3 +369,31 @@ fn update_winning_state_test() {
         assert_eq!(current, expected[i])
     }
 }
+
+#[test]
+fn proven_move_test() {
+    let mut board = crate::endgame::endgame_position(12);
+    let mut mcts = MCTS::new(board.get_turn(), false, 10, 12, board.clone());
+    while !board.is_ended() {
+        if board.get_state() == BoardState::MustPass {
+            board.pass().expect("Pass should be legal");
+            continue;
+        }
+        let (outcome, _) = EndgameSolver::new().solve_wld(&board);
+        let move_ = mcts.search(&board).expect("Search should find a move");
+        board.play_move(&move_).expect("Move should be legal");
+        if outcome != Outcome::Loss {
+            // Le coup joué doit conserver le résultat
+            let (after, _) = EndgameSolver::new().solve_wld(&board);
+            let expected = match outcome {
+                Outcome::Win => Outcome::Loss,
+                _ => Outcome::Draw,
+            };
+            assert_eq!(after, expected);
+        }
+        // L'adversaire joue avec le même arbre
+        if let Some(reply) = board.available_moves(None).first() {
+            board.play_move(reply).expect("Move should be legal");
+        }
+    }
+}
@@ -1,206 +1,732 @@
-use crate::board::{Board, Case};
-use rayon::prelude::*;
+use crate::board::{Board, Move, Symmetry, Undo};
+use crate::error::OthelloError;
+use crate::evaluation::{Evaluator, EvaluatorKind};
+use crate::ordering::{Heuristics, MoveOrdering};
+use crate::rules::{square_coords, square_mask};
+use crate::transposition::{Bound, TableStats, TranspositionTable};
 use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+/// Bound on the absolute value of any evaluation
+pub const INFINITY: i32 = 1_000_000;
+
+/// Longest line the searcher can follow, passes included
+pub const MAX_PLY: usize = 64;
+
+/// Outcome of a search
 #[derive(Clone, Debug)]
-pub struct Tree {
-    pub subtree: Option<Vec<Tree>>,
-    pub moves: usize,
-    pub mov: Option<(usize, usize)>,
-    pub score: (usize, usize),
-    pub cases: [[Case; 8]; 8],
-    pub value: Option<i32>,
+pub struct SearchResult {
+    /// Best move found, `None` if the player to move has to pass
+    pub best_move: Option<(usize, usize)>,
+    /// Value of the position for the player to move
+    pub value: i32,
+    /// Expected line of play starting with the best move, `None` being a pass
+    pub pv: Vec<Option<(usize, usize)>>,
+    /// Number of positions visited, by all the threads
+    pub nodes: u64,
+    /// Depth of the last completed search
+    pub depth: u8,
 }
 
-impl Tree {
-    pub fn from_board(board: &mut Board, mov: Option<(usize, usize)>, depth: u8) -> Self {
-        let moves = board.available_moves(None);
-        if depth == 0 || moves.is_empty() {
-            Tree {
-                subtree: None,
-                moves: moves.len(),
-                cases: board.cases,
-                score: board.score(),
-                value: None,
-                mov,
-            }
-        } else {
-            let mut subtrees = Vec::new();
-            for mov in &moves {
-                board.play_move(mov).unwrap();
-                subtrees.push(Tree::from_board(board, Some(*mov), depth - 1));
-                board.reset(1);
-            }
-            Tree {
-                subtree: Some(subtrees),
-                moves: moves.len(),
-                score: board.score(),
-                cases: board.cases,
-                value: None,
-                mov,
-            }
+impl Display for SearchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pv = self
+            .pv
+            .iter()
+            .map(|&m| Move(m).to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        write!(
+            f,
+            "Depth: {}, Value: {}, Nodes: {}, PV: {}",
+            self.depth, self.value, self.nodes, pv
+        )
+    }
+}
+
+/// Options of the searcher
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchConfig {
+    pub evaluator: EvaluatorKind,
+    pub ordering: MoveOrdering,
+    /// Principal variation search: every move after the first is searched with a null
+    /// window proving it is not better, and searched again only when it is
+    pub pvs: bool,
+    /// Half-width of the window around the value of the previous iteration
+    /// when deepening, the whole range of values being searched when `None`
+    pub aspiration: Option<i32>,
+    /// Number of threads searching the position together (Lazy SMP)
+    pub threads: usize,
+    /// Store the positions under their canonical form, so that symmetric positions
+    /// share their entry, at the cost of computing it at every node. Sound because
+    /// every evaluator gives the same value to the symmetric images of a position.
+    pub canonical_table: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            evaluator: EvaluatorKind::default(),
+            ordering: MoveOrdering::default(),
+            pvs: true,
+            aspiration: Some(ASPIRATION_WINDOW),
+            threads: 1,
+            canonical_table: false,
+        }
+    }
+}
+
+/// Default half-width of the aspiration windows
+pub const ASPIRATION_WINDOW: i32 = 50;
+
+/// Depth-first alpha-beta searcher in negamax form, playing the moves on the board
+/// and taking them back instead of building a tree of positions.
+/// The transposition table is kept from one search to the next.
+///
+/// With several threads, helpers search the same position at the same time in threads
+/// of their own, half of them one ply deeper, and only share their results through the
+/// transposition table (Lazy SMP): the main thread finds the entries they stored and
+/// prunes sooner.
+pub struct Searcher {
+    table: Arc<TranspositionTable>,
+    pub config: SearchConfig,
+    evaluator: Arc<dyn Evaluator>,
+    heuristics: Heuristics,
+    nodes: u64,
+    stats: TableStats,
+    /// Searchers of the other threads, sharing the table
+    helpers: Vec<Searcher>,
+    /// Raised by the main thread once it is done, for the helpers to give up
+    stop: Arc<AtomicBool>,
+    /// Triangular table of the principal variations found at each ply
+    pv_table: [[Option<(usize, usize)>; MAX_PLY]; MAX_PLY],
+    pv_len: [usize; MAX_PLY],
+    /// Move searched first at the root, best move of the previous iteration
+    root_move: Option<(usize, usize)>,
+    /// Time at which a search must be abandoned
+    deadline: Option<Instant>,
+    aborted: bool,
+    /// Whether the search evaluated an unfinished game, a deeper search being useless otherwise
+    reached_horizon: bool,
+}
+
+/// Number of nodes visited between two checks of the clock
+const CLOCK_CHECK_INTERVAL: u64 = 4096;
+
+impl Searcher {
+    /// Create a searcher with a transposition table of `2^table_bits` entries and the given options
+    /// # Returns
+    /// * `Err(OthelloError::Weights)` if the weights of its evaluator cannot be read
+    pub fn with_config(table_bits: u32, config: SearchConfig) -> Result<Self, OthelloError> {
+        let evaluator = config.evaluator.build()?;
+        Ok(Searcher::with_evaluator(table_bits, config, evaluator))
+    }
+
+    /// Create a searcher with an evaluator already built from `config.evaluator`,
+    /// which can be shared with other searchers
+    pub fn with_evaluator(
+        table_bits: u32,
+        config: SearchConfig,
+        evaluator: Arc<dyn Evaluator>,
+    ) -> Self {
+        let table = Arc::new(TranspositionTable::new(table_bits));
+        let stop = Arc::new(AtomicBool::new(false));
+        let helpers = (1..config.threads)
+            .map(|_| {
+                Searcher::helper(
+                    table.clone(),
+                    stop.clone(),
+                    evaluator.clone(),
+                    config.clone(),
+                )
+            })
+            .collect();
+        Searcher::helper(table, stop, evaluator, config).with_helpers(helpers)
+    }
+
+    fn helper(
+        table: Arc<TranspositionTable>,
+        stop: Arc<AtomicBool>,
+        evaluator: Arc<dyn Evaluator>,
+        config: SearchConfig,
+    ) -> Self {
+        Searcher {
+            table,
+            config,
+            evaluator,
+            heuristics: Heuristics::new(),
+            nodes: 0,
+            stats: TableStats::default(),
+            helpers: Vec::new(),
+            stop,
+            pv_table: [[None; MAX_PLY]; MAX_PLY],
+            pv_len: [0; MAX_PLY],
+            root_move: None,
+            deadline: None,
+            aborted: false,
+            reached_horizon: false,
         }
     }
 
-    pub fn expand_tree(&mut self, mut board: &mut Board, depth: u8) {
-        if depth == 0 {
-            return;
-        }
-        if self.subtree.is_none() {
-            let mut subtree = Vec::new();
-            for m in board.available_moves(None) {
-                board.play_move(&m).unwrap();
-                subtree.push(Tree::from_board(&mut board, Some(m), depth - 1));
-                board.reset(1);
+    fn with_helpers(self, helpers: Vec<Searcher>) -> Self {
+        Searcher { helpers, ..self }
+    }
+
+    /// Usage of the transposition table by all the threads
+    pub fn table_stats(&self) -> TableStats {
+        let mut stats = self.stats;
+        for helper in &self.helpers {
+            stats += helper.stats;
+        }
+        stats
+    }
+
+    fn total_nodes(&self) -> u64 {
+        self.nodes + self.helpers.iter().map(|helper| helper.nodes).sum::<u64>()
+    }
+
+    /// Search the best move of the player to move
+    /// # Arguments
+    /// * `board` - The position to search
+    /// * `depth` - The number of plies to look ahead, passes included
+    /// # Returns
+    /// * `Err(OthelloError::Engine)` if the search was stopped before its end
+    pub fn search(&mut self, board: &Board, depth: u8) -> Result<SearchResult, OthelloError> {
+        self.table.new_search();
+        self.nodes = 0;
+        self.heuristics.new_search();
+        for helper in self.helpers.iter_mut() {
+            helper.nodes = 0;
+            helper.heuristics.new_search();
+        }
+        self.search_depth(board, depth, None)
+            .ok_or_else(|| OthelloError::Engine("the search was stopped".to_string()))
+    }
+
+    /// Search with increasing depths until the time budget runs out
+    /// # Arguments
+    /// * `board` - The position to search
+    /// * `budget` - The time allowed for the search
+    /// * `max_depth` - The depth at which to stop even if time remains
+    /// # Returns
+    /// * The result of the deepest search that could be completed, depth 1 always being completed
+    pub fn search_timed(
+        &mut self,
+        board: &Board,
+        budget: Duration,
+        max_depth: u8,
+    ) -> Result<SearchResult, OthelloError> {
+        let start = Instant::now();
+        let mut result = self.search(board, 1)?;
+        self.deadline = Some(start + budget);
+        for depth in 2..=max_depth.min(MAX_PLY as u8 - 1) {
+            // Chaque itération prend plus de temps que toutes les précédentes réunies
+            if !self.reached_horizon || start.elapsed() * 2 > budget {
+                break;
             }
-            self.subtree = Some(subtree);
-        } else {
-            for subt in self.subtree.as_mut().unwrap() {
-                board.play_move(&subt.mov.unwrap()).unwrap();
-                subt.expand_tree(&mut board, depth - 1);
-                board.reset(1);
+            self.root_move = result.best_move;
+            match self.search_depth(board, depth, Some(result.value)) {
+                Some(deeper) => result = deeper,
+                None => break,
             }
         }
+        self.deadline = None;
+        self.root_move = None;
+        result.nodes = self.total_nodes();
+        Ok(result)
     }
 
-    pub fn best_move(&mut self, color: Case, board: &Board, depth: u8) -> (usize, usize) {
-        self.expand_tree(&mut board.clone(), depth);
-        let m = minimax(self, color).mov.unwrap();
-        // on coupe l'arbre
-        for i in 0..self.moves {
-            if self.subtree.as_ref().unwrap()[i].mov.unwrap() == m {
-                let subt = self.subtree.as_ref().unwrap()[i].clone();
-                self.subtree = subt.subtree;
-                self.moves = subt.moves;
-                self.cases = subt.cases;
-                self.score = subt.score;
-                self.value = subt.value;
-                self.mov = subt.mov;
-                return m;
-            }
+    /// Search at a given depth, with the helpers if there are any
+    /// # Arguments
+    /// * `guess` - The expected value, around which an aspiration window is tried first
+    /// # Returns
+    /// * `None` if the deadline was reached before the end of the search
+    fn search_depth(
+        &mut self,
+        board: &Board,
+        depth: u8,
+        guess: Option<i32>,
+    ) -> Option<SearchResult> {
+        if self.helpers.is_empty() {
+            return self.search_alone(board, depth, guess);
         }
-        panic!("No move found");
+        let mut helpers = std::mem::take(&mut self.helpers);
+        let root_move = self.root_move;
+        self.stop.store(false, Ordering::Relaxed);
+        // Des threads à eux plutôt que ceux de rayon, qui peuvent être occupés par d'autres parties
+        let result = std::thread::scope(|s| {
+            for (i, helper) in helpers.iter_mut().enumerate() {
+                helper.root_move = root_move;
+                s.spawn(move || {
+                    // Leur résultat ne sert que par la table
+                    helper.search_alone(board, depth + (i % 2 == 0) as u8, guess);
+                });
+            }
+            let result = self.search_alone(board, depth, guess);
+            self.stop.store(true, Ordering::Relaxed);
+            result
+        });
+        self.helpers = helpers;
+        result.map(|result| SearchResult {
+            nodes: self.total_nodes(),
+            ..result
+        })
     }
-}
 
-impl Display for Tree {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut res = String::new();
-        fn rec(tree: &Tree, depth: u8, res: &mut String) {
-            for _ in 0..depth {
-                res.push_str("   ");
+    /// Search at a given depth in the current thread
+    fn search_alone(
+        &mut self,
+        board: &Board,
+        depth: u8,
+        guess: Option<i32>,
+    ) -> Option<SearchResult> {
+        let depth = depth.clamp(1, MAX_PLY as u8 - 1);
+        self.aborted = false;
+        self.reached_horizon = false;
+        let (mut alpha, mut beta) = match (guess, self.config.aspiration) {
+            (Some(guess), Some(delta)) => (guess - delta, guess + delta),
+            _ => (-INFINITY, INFINITY),
+        };
+        let mut board = board.clone();
+        let value = loop {
+            let value = self.negamax(&mut board, depth, alpha, beta, 0);
+            if self.aborted {
+                return None;
             }
-            res.push_str(&format!(
-                " Moves: {}, Score: {:?}, Value: {:?}, Move: {:?}",
-                tree.moves, tree.score, tree.value, tree.mov
-            ));
-            res.push('\n');
-            if let Some(subtree) = &tree.subtree {
-                for sub in subtree {
-                    rec(sub, depth + 1, res);
-                }
+            // En dehors de la fenêtre on ne connaît qu'une borne : on l'élargit du côté raté
+            if value <= alpha && alpha > -INFINITY {
+                alpha = -INFINITY;
+            } else if value >= beta && beta < INFINITY {
+                beta = INFINITY;
+            } else {
+                break value;
             }
+        };
+        let pv = self.pv_table[0][..self.pv_len[0]].to_vec();
+        Some(SearchResult {
+            best_move: pv.first().copied().flatten(),
+            value,
+            pv,
+            nodes: self.nodes,
+            depth,
+        })
+    }
+
+    /// Check the clock from time to time, flagging the search as aborted once past the deadline
+    #[inline]
+    fn out_of_time(&mut self) -> bool {
+        if !self.aborted && self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            self.aborted = self.stop.load(Ordering::Relaxed)
+                || self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline);
+        }
+        self.aborted
+    }
+
+    /// Key of a position in the transposition table, and symmetry leading from
+    /// the position to the one whose best move is stored
+    fn table_key(&self, board: &Board) -> (u64, Symmetry) {
+        if self.config.canonical_table {
+            let (canonical, symmetry) = board.canonical();
+            (canonical.zobrist(), symmetry)
+        } else {
+            (board.zobrist(), Symmetry::IDENTITY)
         }
-        rec(self, 0, &mut res);
-        write!(f, "{}", res)
     }
-}
 
-pub const PLACEMENT_SCORE: [[isize; 8]; 8] = [
-    [256, -8, 16, 16, 16, 16, -8, 256],
-    [-8, -8, -4, -4, -4, -4, -8, -8],
-    [16, -4, 0, 0, 0, 0, -4, 16],
-    [16, -4, 0, 0, 0, 0, -4, 16],
-    [16, -4, 0, 0, 0, 0, -4, 16],
-    [16, -4, 0, 0, 0, 0, -4, 16],
-    [-8, -8, -4, -4, -4, -4, -8, -8],
-    [256, -8, 16, 16, 16, 16, -8, 256],
-];
-
-pub fn minimax(tree: &mut Tree, color: Case) -> Tree {
-    pub fn minimax_rec(
-        tree: &mut Tree,
-        max_color: Case,
-        current_color: Case,
+    /// Save `bmove` followed by the principal variation of the next ply
+    fn update_pv(&mut self, ply: usize, bmove: Option<(usize, usize)>) {
+        self.pv_table[ply][ply] = bmove;
+        let len = self.pv_len[ply + 1].max(ply + 1);
+        for i in ply + 1..len {
+            self.pv_table[ply][i] = self.pv_table[ply + 1][i];
+        }
+        self.pv_len[ply] = len;
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: u8,
         mut alpha: i32,
-        mut beta: i32,
+        beta: i32,
+        ply: usize,
     ) -> i32 {
-        if tree.moves == 0 || tree.subtree.is_none() {
-            let val = evaluate(tree, max_color);
-            tree.value = Some(val);
+        self.nodes += 1;
+        self.pv_len[ply] = ply;
+        if self.out_of_time() {
+            return 0;
+        }
+        let color = board.get_turn();
+        let moves = board.moves_mask();
+        if moves == 0 {
+            if board.opponent_moves_mask() == 0 {
+                return self.evaluator.evaluate(board, color);
+            }
+            if depth == 0 {
+                self.reached_horizon = true;
+                return self.evaluator.evaluate(board, color);
+            }
+            // Le joueur passe son tour
+            board.make_move_unchecked(Undo::PASS);
+            let val = -self.negamax(board, depth - 1, -beta, -alpha, ply + 1);
+            board.unmake_move(Undo::PASS);
+            if !self.aborted {
+                self.update_pv(ply, None);
+            }
             return val;
         }
-        let mut best = if current_color == max_color {
-            i32::MIN
-        } else {
-            i32::MAX
-        };
-        for subtree in tree.subtree.as_mut().unwrap() {
-            let val = minimax_rec(subtree, max_color, current_color.opponent(), alpha, beta);
-            if max_color == current_color {
-                best = best.max(val);
-                alpha = alpha.max(val);
-                if best >= beta {
-                    break;
+        if depth == 0 {
+            self.reached_horizon = true;
+            return self.evaluator.evaluate(board, color);
+        }
+
+        let (alpha_orig, (key, symmetry)) = (alpha, self.table_key(board));
+        let entry = self.table.probe(key);
+        self.stats.probes += 1;
+        let mut hash_move = 0;
+        if let Some(entry) = entry {
+            self.stats.hits += 1;
+            if let (true, Some(val)) = (ply > 0, entry.cutoff(depth, alpha, beta)) {
+                self.stats.cutoffs += 1;
+                // The value may rely on an evaluation at the horizon of the stored search
+                self.reached_horizon = true;
+                return val;
+            }
+            // On cherche d'abord le meilleur coup d'une recherche précédente
+            if let Some(m) = entry.best_move {
+                hash_move = square_mask(symmetry.inverse().apply_move(m));
+            }
+        }
+        if let (0, Some(m)) = (ply, self.root_move) {
+            hash_move = square_mask(m);
+        }
+
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let moves =
+            self.heuristics
+                .sort(&self.config.ordering, board, moves, hash_move, ply, depth);
+        for square in moves {
+            let bmove = square_coords(square.trailing_zeros());
+            let undo = board.undo_of(square);
+            board.make_move_unchecked(undo);
+            let val = if self.config.pvs && best_move.is_some() {
+                let val = -self.negamax(board, depth - 1, -alpha - 1, -alpha, ply + 1);
+                if val > alpha && val < beta && !self.aborted {
+                    -self.negamax(board, depth - 1, -beta, -alpha, ply + 1)
+                } else {
+                    val
                 }
             } else {
-                best = best.min(val);
-                beta = beta.min(val);
-                if best <= alpha {
-                    break;
+                -self.negamax(board, depth - 1, -beta, -alpha, ply + 1)
+            };
+            board.unmake_move(undo);
+            if self.aborted {
+                return 0;
+            }
+
+            if val > best {
+                best = val;
+                best_move = Some(bmove);
+                if val > alpha {
+                    alpha = val;
+                    self.update_pv(ply, Some(bmove));
+                    if alpha >= beta {
+                        self.heuristics.record_cutoff(color, square, ply, depth);
+                        break;
+                    }
                 }
             }
         }
-        tree.value = Some(best);
+        let bound = Bound::from_window(best, alpha_orig, beta);
+        let stored_move = best_move.map(|m| symmetry.apply_move(m));
+        if self.table.store(key, depth, best, bound, stored_move) {
+            self.stats.stores += 1;
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+use crate::evaluation::PlacementEvaluator;
+
+#[test]
+fn search_matches_minimax_test() {
+    // Plain minimax without any pruning
+    fn minimax(board: &mut Board, depth: u8) -> i32 {
+        let moves = board.available_moves(None);
+        if depth == 0 || board.is_ended() {
+            return PlacementEvaluator.evaluate(board, board.get_turn());
+        }
+        if moves.is_empty() {
+            let undo = board.make_move(None).unwrap();
+            let val = -minimax(board, depth - 1);
+            board.unmake_move(undo);
+            return val;
+        }
+        let mut best = -INFINITY;
+        for m in moves {
+            let undo = board.make_move(Some(m)).unwrap();
+            best = best.max(-minimax(board, depth - 1));
+            board.unmake_move(undo);
+        }
         best
     }
 
-    let best = minimax_rec(tree, color, color, i32::MIN, i32::MAX);
-    let best_tree = tree
-        .subtree
-        .as_ref()
-        .unwrap()
-        .par_iter()
-        .find_any(|x| x.value.unwrap() == best)
-        .unwrap()
-        .clone();
+    let mut board = Board::new();
+    for m in [(2, 3), (2, 2), (3, 2), (2, 4)] {
+        board.play_move(&m).expect("Move should be legal");
+        for depth in 1..5 {
+            // Entries of deeper searches would change the values
+            let result = Searcher::with_config(12, SearchConfig::default())
+                .unwrap()
+                .search(&board, depth)
+                .unwrap();
+            assert_eq!(result.value, minimax(&mut board.clone(), depth));
+            assert_eq!(result.pv[0], result.best_move);
+            assert!(result.pv.len() <= depth as usize);
+            let mut line = board.clone();
+            for m in &result.pv {
+                line.play(*m).expect("PV should be legal");
+            }
+        }
+    }
+}
+
+#[test]
+fn search_timed_test() {
+    let mut board = Board::new();
+    board.play_move(&(2, 3)).expect("Move should be legal");
+    let mut searcher = Searcher::with_config(16, SearchConfig::default()).unwrap();
+    let start = Instant::now();
+    let result = searcher
+        .search_timed(&board, Duration::from_millis(50), MAX_PLY as u8)
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(result.depth >= 1);
+    assert!(board
+        .available_moves(None)
+        .contains(&result.best_move.unwrap()));
+    // Deepening stops at the maximum depth when time remains
+    let result = searcher
+        .search_timed(&board, Duration::from_secs(60), 3)
+        .unwrap();
+    assert_eq!(result.depth, 3);
+}
 
-    best_tree
+#[test]
+fn move_ordering_test() {
+    let mut positions = Vec::new();
+    let mut board = Board::new();
+    for _ in 0..8 {
+        let moves = board.available_moves(None);
+        board
+            .play_move(&moves[moves.len() / 2])
+            .expect("Move should be legal");
+        positions.push(board.clone());
+    }
+    let mut nodes = (0, 0);
+    for board in &positions {
+        let config = SearchConfig {
+            evaluator: EvaluatorKind::Placement,
+            ordering: MoveOrdering::NONE,
+            pvs: false,
+            aspiration: None,
+            threads: 1,
+            canonical_table: false,
+        };
+        let unordered = Searcher::with_config(16, config.clone())
+            .unwrap()
+            .search(board, 5)
+            .unwrap();
+        let config = SearchConfig {
+            ordering: MoveOrdering::ALL,
+            ..config
+        };
+        let ordered = Searcher::with_config(16, config)
+            .unwrap()
+            .search(board, 5)
+            .unwrap();
+        assert_eq!(ordered.value, unordered.value);
+        nodes.0 += unordered.nodes;
+        nodes.1 += ordered.nodes;
+    }
+    assert!(nodes.1 < nodes.0, "{:?}", nodes);
 }
-/// (white,black)
-pub fn matrix_eval(cases: &[[Case; 8]; 8]) -> (isize, isize) {
-    let mut res = (0, 0);
-    for i in 0..8 {
-        for j in 0..8 {
-            if cases[i][j] == Case::White {
-                res.0 += PLACEMENT_SCORE[i][j];
-            } else if cases[i][j] == Case::Black {
-                res.1 += PLACEMENT_SCORE[i][j];
+
+#[test]
+fn pvs_and_aspiration_test() {
+    let mut board = Board::new();
+    let alpha_beta = SearchConfig {
+        pvs: false,
+        aspiration: None,
+        ..SearchConfig::default()
+    };
+    let pvs_only = SearchConfig {
+        aspiration: None,
+        ..SearchConfig::default()
+    };
+    // Nodes with alpha-beta, PVS, and PVS in a window around the value. The null windows
+    // only pay for their re-searches from a few plies deep.
+    let mut nodes = (0, 0, 0);
+    for _ in 0..8 {
+        let moves = board.available_moves(None);
+        board
+            .play_move(&moves[moves.len() / 3])
+            .expect("Move should be legal");
+        let expected = Searcher::with_config(16, alpha_beta.clone())
+            .unwrap()
+            .search(&board, 7)
+            .unwrap();
+        let pvs = Searcher::with_config(16, pvs_only.clone())
+            .unwrap()
+            .search(&board, 7)
+            .unwrap();
+        assert_eq!(pvs.value, expected.value);
+        nodes.0 += expected.nodes;
+        nodes.1 += pvs.nodes;
+        // Windows missing the value on either side, or containing it
+        for guess in [expected.value - 500, expected.value, expected.value + 500] {
+            let mut searcher = Searcher::with_config(16, SearchConfig::default()).unwrap();
+            let result = searcher.search_depth(&board, 7, Some(guess)).unwrap();
+            assert_eq!(result.value, expected.value);
+            if guess == expected.value {
+                nodes.2 += result.nodes;
             }
         }
     }
-    res
+    assert!(nodes.1 < nodes.0, "{:?}", nodes);
+    assert!(nodes.2 < nodes.1, "{:?}", nodes);
 }
 
-pub fn evaluate(tree: &Tree, color: Case) -> i32 {
-    let score = tree.score;
-    let filled = score.0 + score.1;
-    let balance = if color == Case::Black {
-        score.0 as i32 - score.1 as i32
-    } else {
-        score.1 as i32 - score.0 as i32
+#[test]
+fn parallel_search_test() {
+    let config = SearchConfig {
+        threads: 3,
+        ..SearchConfig::default()
     };
-    if tree.moves == 0 {
-        10000 * balance.signum() + balance
-    } else {
-        let state = filled / 16; // Découpe la partie en plusieurs phases
-        let mut result = balance << state; // On attribue une importance grandissante au score en fonction de l'avancement de la partie
-        let matrix = matrix_eval(&tree.cases);
-        result += if color == Case::Black {
-            matrix.1 as i32 - matrix.0 as i32 // Différence de score de placement
-        } else {
-            matrix.0 as i32 - matrix.1 as i32
+    let mut searcher = Searcher::with_config(16, config.clone()).unwrap();
+    let mut board = Board::new();
+    for _ in 0..3 {
+        let result = searcher.search(&board, 5).unwrap();
+        // The helpers stored deeper entries, the value may differ from a single thread
+        assert!(board
+            .available_moves(None)
+            .contains(&result.best_move.unwrap()));
+        assert!(result.nodes > searcher.nodes);
+        assert_eq!(searcher.helpers.len(), 2);
+        let mut line = board.clone();
+        for m in &result.pv {
+            line.play(*m).expect("PV should be legal");
+        }
+        board.play(result.best_move).expect("Move should be legal");
+    }
+    let result = searcher
+        .search_timed(&board, Duration::from_millis(50), MAX_PLY as u8)
+        .unwrap();
+    assert!(result.depth >= 1);
+
+    // Searched to the end of the game, the helpers' deeper entries cannot change the value
+    let mut board = crate::endgame::endgame_position(10);
+    while !board.is_ended() {
+        let single = Searcher::with_config(16, SearchConfig::default())
+            .unwrap()
+            .search(&board, 20)
+            .unwrap();
+        let parallel = Searcher::with_config(16, config.clone())
+            .unwrap()
+            .search(&board, 20)
+            .unwrap();
+        assert_eq!(parallel.value, single.value);
+        // Parmi des coups de même valeur, chaque thread peut trouver le sien
+        let mut after = board.clone();
+        after.play(parallel.best_move).unwrap();
+        let reply = Searcher::with_config(16, SearchConfig::default())
+            .unwrap()
+            .search(&after, 20)
+            .unwrap();
+        assert_eq!(-reply.value, single.value);
+        board.play(single.best_move).unwrap();
+    }
+}
+
+#[test]
+#[ignore = "needs several cores, run with --release --ignored"]
+fn parallel_speedup_test() {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get().min(4));
+    assert!(threads > 1, "a single core cannot speed the search up");
+    let time_to_depth = |threads: usize| {
+        let config = SearchConfig {
+            threads,
+            ..SearchConfig::default()
+        };
+        let start = Instant::now();
+        for board in crate::bench::positions() {
+            Searcher::with_config(20, config.clone())
+                .unwrap()
+                .search(&board, 10)
+                .unwrap();
+        }
+        start.elapsed()
+    };
+    let (single, parallel) = (time_to_depth(1), time_to_depth(threads));
+    assert!(
+        parallel < single,
+        "{} threads: {:?}, 1 thread: {:?}",
+        threads,
+        parallel,
+        single
+    );
+}
+
+#[test]
+fn canonical_table_test() {
+    use crate::pattern::PatternEvaluator;
+    use rand::{Rng, SeedableRng};
+
+    // Patterns with random weights, which the shared entries must not fool either
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let mut patterns = PatternEvaluator::new();
+    patterns
+        .weights
+        .iter_mut()
+        .for_each(|w| *w = rng.gen_range(-500..500));
+    let path = std::env::temp_dir().join("othello_canonical_table_test.bin");
+    patterns.save(&path).unwrap();
+    for evaluator in [
+        EvaluatorKind::Placement,
+        EvaluatorKind::Pattern(path.clone()),
+    ] {
+        let plain = SearchConfig {
+            evaluator,
+            ..SearchConfig::default()
+        };
+        let config = SearchConfig {
+            canonical_table: true,
+            ..plain.clone()
         };
-        result
+        let mut board = Board::new();
+        for _ in 0..4 {
+            let expected = Searcher::with_config(16, plain.clone())
+                .unwrap()
+                .search(&board, 5)
+                .unwrap();
+            let canonical = Searcher::with_config(16, config.clone())
+                .unwrap()
+                .search(&board, 5)
+                .unwrap();
+            assert_eq!(canonical.value, expected.value);
+            assert!(board
+                .available_moves(None)
+                .contains(&canonical.best_move.unwrap()));
+            board
+                .play(expected.best_move)
+                .expect("Move should be legal");
+        }
     }
+    std::fs::remove_file(&path).unwrap();
 }
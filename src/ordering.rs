@@ -0,0 +1,165 @@
+use crate::board::{Board, Case};
+use crate::minimax::MAX_PLY;
+
+/// Heuristics used by the searcher to sort the moves before searching them,
+/// the sooner the best move is searched the more alpha-beta can prune
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveOrdering {
+    /// Best move stored in the transposition table, or found by the previous iteration at the root
+    pub hash_move: bool,
+    /// Moves which caused a cutoff at the same ply in another branch
+    pub killers: bool,
+    /// Moves which caused many cutoffs anywhere in the tree
+    pub history: bool,
+    /// Moves leaving the fewest replies to the opponent first (fastest-first)
+    pub mobility: bool,
+}
+
+impl MoveOrdering {
+    /// Search the moves without sorting them
+    #[cfg(test)]
+    pub const NONE: MoveOrdering = MoveOrdering {
+        hash_move: false,
+        killers: false,
+        history: false,
+        mobility: false,
+    };
+
+    pub const ALL: MoveOrdering = MoveOrdering {
+        hash_move: true,
+        killers: true,
+        history: true,
+        mobility: true,
+    };
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering::ALL
+    }
+}
+
+const HASH_MOVE_SCORE: i32 = i32::MAX;
+const KILLER_SCORE: i32 = 1 << 30;
+/// History scores are kept below this bound so that they only break mobility ties
+const HISTORY_MAX: u32 = (1 << 16) - 1;
+
+/// Killer moves and history counters gathered during the searches
+pub struct Heuristics {
+    /// Two last moves that caused a cutoff at each ply
+    killers: [[u64; 2]; MAX_PLY],
+    /// Cutoffs caused by each square, for black and for white
+    history: [[u32; 64]; 2],
+}
+
+#[inline]
+fn color_index(color: Case) -> usize {
+    match color {
+        Case::White => 1,
+        _ => 0,
+    }
+}
+
+impl Heuristics {
+    pub fn new() -> Self {
+        Heuristics {
+            killers: [[0; 2]; MAX_PLY],
+            history: [[0; 64]; 2],
+        }
+    }
+
+    /// Forget the killers and age the history before searching a new position
+    pub fn new_search(&mut self) {
+        self.killers = [[0; 2]; MAX_PLY];
+        for counters in self.history.iter_mut() {
+            for counter in counters.iter_mut() {
+                *counter /= 2;
+            }
+        }
+    }
+
+    /// Sort the legal moves of a position
+    /// # Arguments
+    /// * `moves` - The legal moves of the player to move
+    /// * `hash_move` - The bit of the move to search first, 0 if there is none
+    /// * `ply` - The distance to the root of the search
+    /// * `depth` - The remaining depth
+    pub fn sort(
+        &self,
+        ordering: &MoveOrdering,
+        board: &mut Board,
+        mut moves: u64,
+        hash_move: u64,
+        ply: usize,
+        depth: u8,
+    ) -> MoveList {
+        let mut list = MoveList {
+            moves: [(0, 0); 64],
+            len: 0,
+        };
+        let history = &self.history[color_index(board.get_turn())];
+        while moves != 0 {
+            let square = moves & moves.wrapping_neg();
+            moves ^= square;
+            let score = if ordering.hash_move && square == hash_move {
+                HASH_MOVE_SCORE
+            } else if ordering.killers && square == self.killers[ply][0] {
+                KILLER_SCORE
+            } else if ordering.killers && square == self.killers[ply][1] {
+                KILLER_SCORE - 1
+            } else {
+                let mut score = 0;
+                // Inutile aux feuilles : les coups suivants ne sont pas cherchés
+                if ordering.mobility && depth > 1 {
+                    let undo = board.undo_of(square);
+                    board.make_move_unchecked(undo);
+                    score -= (board.moves_mask().count_ones() as i32) << 16;
+                    board.unmake_move(undo);
+                }
+                if ordering.history {
+                    score += history[square.trailing_zeros() as usize].min(HISTORY_MAX) as i32;
+                }
+                score
+            };
+            list.moves[list.len] = (square, score);
+            list.len += 1;
+        }
+        list
+    }
+
+    /// Remember a move which caused a beta cutoff
+    pub fn record_cutoff(&mut self, color: Case, square: u64, ply: usize, depth: u8) {
+        if self.killers[ply][0] != square {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = square;
+        }
+        let counter = &mut self.history[color_index(color)][square.trailing_zeros() as usize];
+        *counter = counter.saturating_add(depth as u32 * depth as u32);
+    }
+}
+
+/// Moves of a position with their ordering score, yielded best first
+pub struct MoveList {
+    moves: [(u64, i32); 64],
+    len: usize,
+}
+
+impl Iterator for MoveList {
+    type Item = u64;
+
+    /// Selection sort, one move at a time since a cutoff often makes the next ones useless
+    fn next(&mut self) -> Option<u64> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut best = 0;
+        for i in 1..self.len {
+            if self.moves[i].1 > self.moves[best].1 {
+                best = i;
+            }
+        }
+        self.len -= 1;
+        self.moves.swap(best, self.len);
+        Some(self.moves[self.len].0)
+    }
+}
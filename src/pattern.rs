@@ -0,0 +1,314 @@
+use crate::board::{Board, Case, Symmetry};
+use crate::evaluation::{final_value, Evaluator};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Number of game phases with their own weights, by number of empty squares
+pub const PHASES: usize = 12;
+
+/// Header of a weights file
+const MAGIC: &[u8; 8] = b"OTHPAT02";
+
+/// Squares of each pattern, as placed in the top left corner of the board.
+/// Every symmetric image of a pattern shares its weights.
+const PATTERNS: [&[(usize, usize)]; 8] = [
+    // Bord et ses deux cases X
+    &[
+        (1, 1),
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        (0, 5),
+        (0, 6),
+        (0, 7),
+        (1, 6),
+    ],
+    // Coin 3x3
+    &[
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (2, 0),
+        (2, 1),
+        (2, 2),
+    ],
+    // Coin 2x5
+    &[
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+    ],
+    // Diagonales de 8 à 4 cases
+    &[
+        (0, 0),
+        (1, 1),
+        (2, 2),
+        (3, 3),
+        (4, 4),
+        (5, 5),
+        (6, 6),
+        (7, 7),
+    ],
+    &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (5, 6), (6, 7)],
+    &[(0, 2), (1, 3), (2, 4), (3, 5), (4, 6), (5, 7)],
+    &[(0, 3), (1, 4), (2, 5), (3, 6), (4, 7)],
+    &[(0, 4), (1, 5), (2, 6), (3, 7)],
+];
+
+/// Phase of the game, giving the weights to use
+#[inline]
+pub fn phase(board: &Board) -> usize {
+    (board.empties() as usize * PHASES / 61).min(PHASES - 1)
+}
+
+/// Every placement of the patterns on the board, and where their weights start
+pub struct Patterns {
+    /// Squares of each placement, and offset of the weights of its pattern
+    instances: Vec<(Vec<u8>, usize)>,
+    /// Weight of each configuration of the patterns, shared with the configurations
+    /// read in another order on the same squares
+    folded: Vec<u32>,
+    /// Number of weights of a phase
+    pub size: usize,
+}
+
+/// Index of the smallest configuration among the ones read on the same squares in
+/// another order, for every configuration of a pattern
+/// # Arguments
+/// * `pattern` - The squares of the pattern
+/// * `offset` - The offset of its weights
+fn fold(pattern: &[(usize, usize)], offset: usize) -> Vec<u32> {
+    // Symétries envoyant le motif sur lui-même : position de l'image de chaque case
+    let orders: Vec<Vec<usize>> = Symmetry::all()
+        .filter_map(|symmetry| {
+            pattern
+                .iter()
+                .map(|&square| {
+                    pattern
+                        .iter()
+                        .position(|&s| s == symmetry.apply_move(square))
+                })
+                .collect()
+        })
+        .collect();
+    let len = pattern.len();
+    let digit = |index: usize, k: usize| index / 3_usize.pow((len - 1 - k) as u32) % 3;
+    (0..3_usize.pow(len as u32))
+        .map(|index| {
+            let folded = orders
+                .iter()
+                .map(|order| {
+                    (0..len).fold(0, |image, k| {
+                        let from = order.iter().position(|&to| to == k).unwrap();
+                        image * 3 + digit(index, from)
+                    })
+                })
+                .min()
+                .unwrap();
+            (offset + folded) as u32
+        })
+        .collect()
+}
+
+impl Patterns {
+    pub fn new() -> Self {
+        let mut instances = Vec::new();
+        let mut folded = Vec::new();
+        let mut size = 0;
+        for pattern in PATTERNS {
+            folded.extend(fold(pattern, size));
+            let mut placed: Vec<Vec<u8>> = Vec::new();
+            for symmetry in Symmetry::all() {
+                let squares: Vec<u8> = pattern
+                    .iter()
+                    .map(|&square| {
+                        let (r, c) = symmetry.apply_move(square);
+                        (r * 8 + c) as u8
+                    })
+                    .collect();
+                // Une même ligne parcourue dans l'autre sens n'est comptée qu'une fois
+                let mut set = squares.clone();
+                set.sort_unstable();
+                if !placed.iter().any(|other| {
+                    let mut other = other.clone();
+                    other.sort_unstable();
+                    other == set
+                }) {
+                    placed.push(squares);
+                }
+            }
+            instances.extend(placed.into_iter().map(|squares| (squares, size)));
+            size += 3_usize.pow(pattern.len() as u32);
+        }
+        Patterns {
+            instances,
+            folded,
+            size,
+        }
+    }
+
+    /// Index in the weights of a phase of every placement of the patterns,
+    /// each square counting 0 when empty, 1 for `player` and 2 for `opponent`,
+    /// the symmetric images of a position giving the same indices
+    pub fn indices(&self, player: u64, opponent: u64) -> impl Iterator<Item = usize> + '_ {
+        self.instances.iter().map(move |(squares, offset)| {
+            let index = squares.iter().fold(0, |index, &square| {
+                index * 3 + (player >> square & 1) as usize + 2 * (opponent >> square & 1) as usize
+            });
+            self.folded[offset + index] as usize
+        })
+    }
+}
+
+/// Evaluation summing the weights of the configurations of the patterns,
+/// in hundredths of a disc, with weights fitted on games for each phase
+pub struct PatternEvaluator {
+    pub patterns: Patterns,
+    /// Weights of every phase one after the other
+    pub weights: Vec<i16>,
+}
+
+impl PatternEvaluator {
+    /// Create an evaluator with every weight to 0
+    pub fn new() -> Self {
+        let patterns = Patterns::new();
+        let weights = vec![0; patterns.size * PHASES];
+        PatternEvaluator { patterns, weights }
+    }
+
+    /// Read the weights from a file written by `save`
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut evaluator = PatternEvaluator::new();
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0; 12];
+        file.read_exact(&mut header)?;
+        let phases = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        if &header[..8] != MAGIC || phases != PHASES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a weights file of this version",
+            ));
+        }
+        let mut bytes = vec![0; evaluator.weights.len() * 2];
+        file.read_exact(&mut bytes)?;
+        for (weight, bytes) in evaluator.weights.iter_mut().zip(bytes.chunks_exact(2)) {
+            *weight = i16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(evaluator)
+    }
+
+    /// Write the weights to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&(PHASES as u32).to_le_bytes())?;
+        for weight in &self.weights {
+            file.write_all(&weight.to_le_bytes())?;
+        }
+        file.flush()
+    }
+}
+
+impl Evaluator for PatternEvaluator {
+    fn evaluate(&self, board: &Board, color: Case) -> i32 {
+        let (player, opponent) = (board.discs(color), board.discs(color.opponent()));
+        if board.is_ended() {
+            return final_value(player.count_ones() as i32 - opponent.count_ones() as i32);
+        }
+        let weights = &self.weights[phase(board) * self.patterns.size..];
+        self.patterns
+            .indices(player, opponent)
+            .map(|index| weights[index] as i32)
+            .sum()
+    }
+}
+
+#[test]
+fn patterns_test() {
+    let patterns = Patterns::new();
+    // 4 bords, 4 coins 3x3, 8 coins 2x5, 2 grandes diagonales et 4 de chaque autre longueur
+    assert_eq!(patterns.instances.len(), 4 + 4 + 8 + 2 + 4 * 4);
+    assert_eq!(
+        patterns.size,
+        2 * 3_usize.pow(10) + 3_usize.pow(9) + (4..=8).map(|n| 3_usize.pow(n)).sum::<usize>()
+    );
+    // Every square is covered, and the start position is seen the same from both sides
+    let covered = patterns
+        .instances
+        .iter()
+        .flat_map(|(squares, _)| squares)
+        .fold(0_u64, |covered, &square| covered | 1 << square);
+    assert_eq!(covered, u64::MAX);
+    let board = Board::new();
+    let (black, white) = (board.discs(Case::Black), board.discs(Case::White));
+    let mut from_black: Vec<usize> = patterns.indices(black, white).collect();
+    let mut from_white: Vec<usize> = patterns.indices(white, black).collect();
+    from_black.sort_unstable();
+    from_white.sort_unstable();
+    assert_eq!(from_black, from_white);
+}
+
+#[test]
+fn pattern_weights_test() {
+    let mut evaluator = PatternEvaluator::new();
+    let mut board = Board::new();
+    for m in [(2, 3), (2, 2), (3, 2), (2, 4)] {
+        board.play_move(&m).expect("Move should be legal");
+    }
+    let size = evaluator.patterns.size;
+    let offset = phase(&board) * size;
+    evaluator.weights[offset..offset + size].fill(1);
+    // Bords encore vides, cases X comprises
+    evaluator.weights[offset] = 100;
+    let instances = evaluator.patterns.instances.len() as i32;
+    assert_eq!(
+        evaluator.evaluate(&board, Case::Black),
+        instances - 4 + 4 * 100
+    );
+
+    let path = std::env::temp_dir().join("othello_pattern_weights_test.bin");
+    evaluator.save(&path).expect("Weights should be written");
+    let loaded = PatternEvaluator::load(&path).expect("Weights should be read");
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.weights, evaluator.weights);
+    assert_eq!(loaded.evaluate(&board, Case::Black), instances - 4 + 400);
+
+    // Finished games are valued in hundredths of a disc too
+    let ended = Board::from_position(&format!("{} X", "X".repeat(64))).unwrap();
+    assert_eq!(loaded.evaluate(&ended, Case::White), -final_value(64));
+    assert_eq!(final_value(64) - final_value(63), 100);
+}
+
+#[test]
+fn pattern_symmetry_test() {
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let mut evaluator = PatternEvaluator::new();
+    evaluator.weights.iter_mut().for_each(|w| *w = rng.gen());
+    let mut board = Board::new();
+    while !board.is_ended() {
+        let moves = board.available_moves(None);
+        board
+            .play(moves.get(rng.gen_range(0..moves.len().max(1))).copied())
+            .unwrap();
+        let color = board.get_turn();
+        let value = evaluator.evaluate(&board, color);
+        for symmetry in Symmetry::all() {
+            let image = board.transform(symmetry);
+            assert_eq!(evaluator.evaluate(&image, color), value, "{:?}", symmetry);
+        }
+    }
+}
@@ -1,44 +1,202 @@
 use parking_lot::Mutex;
 use std::io::stdout;
+use std::path::PathBuf;
 use std::process::exit;
-
-use crate::board::{Board, BoardState, Case, EndState};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::board::{Board, BoardState, Case, EndState, Move};
+use crate::book::{Book, BookChoice};
+use crate::endgame::EndgameSolver;
+use crate::error::OthelloError;
+use crate::evaluation::{Evaluator, EvaluatorKind};
+use crate::ggf::{self, GgfGame};
 use crate::mcts;
-use crate::minimax::Tree;
+use crate::minimax::{SearchConfig, Searcher, MAX_PLY};
 use crossterm::cursor::MoveUp;
 
 use rand::seq::SliceRandom;
 use rayon::prelude::*;
-#[derive(Debug, Clone, Copy)]
-#[allow(unused)]
+#[derive(Debug, Clone)]
+#[allow(unused, clippy::upper_case_acronyms)]
 pub enum Strategy {
     Random,
     Greedy,
     Manual,
+    /// `solve_empties` being the number of empty squares from which the
+    /// player solves the end of the game exactly, for every engine,
+    /// and `threads` the number of threads of each search
     Minimax {
         depth: u8,
+        evaluator: EvaluatorKind,
+        solve_empties: u8,
+        threads: usize,
     },
+    /// Iterative deepening minimax given `move_time` per move,
+    /// and spreading `game_time` over the whole game when set
+    MinimaxTimed {
+        move_time: Duration,
+        game_time: Option<Duration>,
+        config: SearchConfig,
+        solve_empties: u8,
+    },
+    /// `wld_empties` being the number of empty squares from which the
+    /// search plays a proven win or draw rather than the most played move
     MCTS {
         playout_budget: usize,
         final_solve: bool,
+        solve_empties: u8,
+        wld_empties: u8,
     },
 }
 
+#[derive(Clone, Copy)]
 #[allow(unused)]
 pub enum PlayStyle {
     Automatic,
     Progressive,
 }
 
+/// Evaluators of the Black and White minimax players, `None` for the other strategies
+type Evaluators = [Option<Arc<dyn Evaluator>>; 2];
+
 pub struct Player {
     strategy: (Strategy, Strategy),
+    /// Opening book of the minimax and MCTS players
+    book: Option<(Arc<Book>, BookChoice)>,
+    /// GGF file where `play_games` writes its games
+    archive: Option<PathBuf>,
+    /// Evaluators of the minimax players, built once and shared by all their games
+    evaluators: Mutex<Option<Evaluators>>,
 }
 
 impl Player {
     /// New players (Black,white)
     pub fn new(strategy: (Strategy, Strategy)) -> Self {
-        Player { strategy }
+        Player {
+            strategy,
+            book: None,
+            archive: None,
+            evaluators: Mutex::new(None),
+        }
+    }
+
+    /// Let the minimax and MCTS players play their opening moves from a book
+    pub fn with_book(self, book: Book, choice: BookChoice) -> Self {
+        Player {
+            book: Some((Arc::new(book), choice)),
+            ..self
+        }
+    }
+    /// Ask the player of the side to move for its move in a position, printing
+    /// the statistics of its search
+    /// # Returns
+    /// * `Ok(None)` if the side to move has to pass or the game is over
+    /// * `Err(OthelloError)` if the player cannot be created
+    pub fn analyze(&self, board: &Board) -> Result<Option<(usize, usize)>, OthelloError> {
+        if board.get_state() != BoardState::Ongoing {
+            return Ok(None);
+        }
+        let color = board.get_turn();
+        let strategy = match color {
+            Case::Black => &self.strategy.0,
+            _ => &self.strategy.1,
+        };
+        let [black, white] = self.evaluators()?;
+        let evaluator = if color == Case::Black { black } else { white };
+        let mut player = new_player_api(
+            strategy,
+            PlayStyle::Progressive,
+            color,
+            board,
+            &self.book,
+            evaluator,
+        )?;
+        let bmove = player.get_move(board)?;
+        if let Some(report) = player.report() {
+            println!("{}", report);
+        }
+        Ok(Some(bmove))
+    }
+
+    /// Write the games of `play_games` to a GGF file
+    pub fn with_archive(self, path: impl Into<PathBuf>) -> Self {
+        Player {
+            archive: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Evaluators of the Black and White minimax players, read the first time only
+    /// # Returns
+    /// * `Err(OthelloError::Weights)` if the weights of one of them cannot be read
+    fn evaluators(&self) -> Result<Evaluators, OthelloError> {
+        let mut evaluators = self.evaluators.lock();
+        if let Some(built) = &*evaluators {
+            return Ok(built.clone());
+        }
+        let built = [
+            build_evaluator(&self.strategy.0)?,
+            build_evaluator(&self.strategy.1)?,
+        ];
+        *evaluators = Some(built.clone());
+        Ok(built)
+    }
+
+    /// Create the Black and White players
+    fn players(
+        &self,
+        playstyle: PlayStyle,
+        board: &Board,
+    ) -> Result<[Box<dyn PlayerApiTrait>; 2], OthelloError> {
+        let [black, white] = self.evaluators()?;
+        Ok([
+            new_player_api(
+                &self.strategy.0,
+                playstyle,
+                Case::Black,
+                board,
+                &self.book,
+                black,
+            )?,
+            new_player_api(
+                &self.strategy.1,
+                playstyle,
+                Case::White,
+                board,
+                &self.book,
+                white,
+            )?,
+        ])
+    }
+
+    /// Replay a recorded game, asking the player of the side to move for its move
+    /// in every position before playing the recorded one
+    /// # Returns
+    /// * The number of recorded moves the players found, and the number of moves asked
+    /// * `Err(OthelloError)` if a player cannot be created, or a recorded move is illegal
+    pub fn replay(
+        &self,
+        start: &Board,
+        moves: &[Option<(usize, usize)>],
+    ) -> Result<(usize, usize), OthelloError> {
+        let mut board = start.clone();
+        let mut players = self.players(PlayStyle::Automatic, start)?;
+        let (mut found, mut asked) = (0, 0);
+        for &bmove in moves {
+            if let Some(bmove) = bmove {
+                let player = &mut players[usize::from(board.get_turn() == Case::White)];
+                found += usize::from(player.get_move(&board)? == bmove);
+                asked += 1;
+            }
+            board.play(bmove)?;
+            for player in &mut players {
+                player.update_board(&board);
+            }
+        }
+        Ok((found, asked))
     }
+
     #[allow(dead_code)]
     pub fn progressive_play(&mut self) {
         let is_win = cfg!(windows);
@@ -46,10 +204,13 @@ impl Player {
         println!("Welcome to Reversi (Othello)! - Rust Edition");
 
         let mut board = Board::new();
-        let mut player1 =
-            new_player_api(self.strategy.0, PlayStyle::Progressive, Case::Black, &board);
-        let mut player2 =
-            new_player_api(self.strategy.1, PlayStyle::Progressive, Case::White, &board);
+        let [mut player1, mut player2] = match self.players(PlayStyle::Progressive, &board) {
+            Ok(players) => players,
+            Err(e) => {
+                eprintln!("Cannot create the players: {}", e);
+                return;
+            }
+        };
         println!();
         println!("{}", board);
         loop {
@@ -59,18 +220,25 @@ impl Player {
                 Case::Empty => unreachable!(),
             };
 
-            let move_ = current_player.get_move(&board);
-
-            let state = board.play_move(&move_).unwrap();
+            let played = current_player
+                .get_move(&board)
+                .and_then(|move_| Ok((move_, board.play_move(&move_)?)));
+            let (move_, state) = match played {
+                Ok(played) => played,
+                Err(e) => {
+                    eprintln!("The game stops: {}", e);
+                    return;
+                }
+            };
 
             println!(
                 "{:?} played {}",
                 if turn == 0 {
-                    self.strategy.0
+                    &self.strategy.0
                 } else {
-                    self.strategy.1
+                    &self.strategy.1
                 },
-                print_coords(&move_)
+                Move(Some(move_))
             );
             println!("{}", board);
 
@@ -79,9 +247,19 @@ impl Player {
                     current_player.update_board(&board);
                     other.update_board(&board);
                 }
+                BoardState::MustPass => {
+                    if let Err(e) = board.pass() {
+                        eprintln!("The game stops: {}", e);
+                        return;
+                    }
+                    println!("{:?} has to pass", board.get_turn().opponent());
+                    current_player.update_board(&board);
+                    other.update_board(&board);
+                }
                 BoardState::Ended(end_state) => {
                     let (black, white) = board.score();
                     println!("Black: {}, White: {}", black, white);
+                    println!("Game: {}", board.transcript());
                     println!(
                         "Winner: {}",
                         match end_state {
@@ -90,14 +268,33 @@ impl Player {
                             EndState::Winner(Case::Empty) => "Draw",
                         }
                     );
+                    for (color, player) in [("Black", &player1), ("White", &player2)] {
+                        if let Some(report) = player.report() {
+                            println!("{}: {}", color, report);
+                        }
+                    }
                     break;
                 }
             }
         }
     }
 
+    /// Play `n` games in parallel, a game where a player fails being left out of the score
+    /// # Returns
+    /// * The number of games won by Black, by White, and drawn
+    /// * `Err(OthelloError)` if the players cannot be created
     #[allow(dead_code)]
-    pub fn play_games(&mut self, n: u32, verbose: bool, length: usize) -> (u32, u32, u32) {
+    pub fn play_games(
+        &mut self,
+        n: u32,
+        verbose: bool,
+        length: usize,
+    ) -> Result<(u32, u32, u32), OthelloError> {
+        // Un joueur qui ne peut être créé ne jouera aucune partie
+        self.evaluators()?;
+        if matches!(self.strategy, (Strategy::Manual, _) | (_, Strategy::Manual)) {
+            return Err(OthelloError::ManualInAutomatic);
+        }
         let score: Mutex<(u32, u32, u32)> = Mutex::new((0, 0, 0));
         if verbose {
             display_score(
@@ -107,99 +304,246 @@ impl Player {
                 (&self.strategy.0, &self.strategy.1),
             );
         }
+        let archived = Mutex::new(Vec::new());
         (0..n).into_par_iter().for_each(|_| {
-            let mut board = Board::new();
-            let mut player1 = new_player_api(
-                self.strategy.0,
-                PlayStyle::Automatic,
-                Case::Black,
-                &board.clone(),
-            );
-            let mut player2 = new_player_api(
-                self.strategy.1,
-                PlayStyle::Automatic,
-                Case::White,
-                &board.clone(),
-            );
-            loop {
-                let (current_player, other) = match board.get_turn() {
-                    Case::Black => (&mut player1, &mut player2),
-                    Case::White => (&mut player2, &mut player1),
-                    Case::Empty => panic!(""),
-                };
-
-                let move_ = current_player.get_move(&board);
-
-                let state = board.play_move(&move_).unwrap();
-
-                if state == BoardState::Ongoing {
-                    current_player.update_board(&board);
-                    other.update_board(&board);
-                } else if let BoardState::Ended(end_state) = state {
-                    let mut locked = score.lock();
-                    match end_state {
-                        EndState::Winner(Case::Black) => locked.0 += 1,
-                        EndState::Winner(Case::White) => locked.1 += 1,
-                        EndState::Winner(Case::Empty) => locked.2 += 1,
-                    }
-                    if verbose {
-                        go_3_lines_up();
-                        display_score(*locked, n, length, (&self.strategy.0, &self.strategy.1));
-                        break;
+            let (board, times) = match self.play_game() {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Game abandoned: {}", e);
+                    return;
+                }
+            };
+            let mut locked = score.lock();
+            match board.current_winner() {
+                Case::Black => locked.0 += 1,
+                Case::White => locked.1 += 1,
+                Case::Empty => locked.2 += 1,
+            }
+            if verbose {
+                go_3_lines_up();
+                display_score(*locked, n, length, (&self.strategy.0, &self.strategy.1));
+            }
+            if self.archive.is_some() {
+                let (black, white) = (
+                    format!("{:?}", self.strategy.0),
+                    format!("{:?}", self.strategy.1),
+                );
+                match GgfGame::new(&black, &white, &Board::new(), &board.history.moves) {
+                    Ok(mut game) => {
+                        for (annotation, time) in game.annotations.iter_mut().zip(&times) {
+                            annotation.time = Some(time.as_secs_f32());
+                        }
+                        archived.lock().push(game);
                     }
+                    Err(e) => eprintln!("Game not archived: {}", e),
                 }
             }
         });
+        if let Some(path) = &self.archive {
+            if let Err(e) = ggf::save(path, &archived.into_inner()) {
+                eprintln!("Cannot write the games to {}: {}", path.display(), e);
+            }
+        }
         let games_result = *score.lock();
 
-        games_result
+        Ok(games_result)
+    }
+
+    /// Play a game between the two players
+    /// # Returns
+    /// * The final position, and the time spent on each move
+    fn play_game(&self) -> Result<(Board, Vec<Duration>), OthelloError> {
+        let mut board = Board::new();
+        let mut players = self.players(PlayStyle::Automatic, &board)?;
+        // Temps de réflexion de chaque coup, pour l'archive
+        let mut times = Vec::new();
+        loop {
+            let player = &mut players[usize::from(board.get_turn() == Case::White)];
+            let start = Instant::now();
+            let move_ = player.get_move(&board)?;
+            times.push(start.elapsed());
+
+            let mut state = board.play_move(&move_)?;
+            if state == BoardState::MustPass {
+                state = board.pass()?;
+                times.push(Duration::ZERO);
+            }
+            if state != BoardState::Ongoing {
+                return Ok((board, times));
+            }
+            for player in &mut players {
+                player.update_board(&board);
+            }
+        }
     }
 }
 
 trait PlayerApiTrait {
     fn update_board(&mut self, board: &Board);
-    fn get_move(&mut self, board: &Board) -> (usize, usize);
+    /// Choose the move to play
+    /// # Returns
+    /// * `Err(OthelloError)` if the player cannot move, or its engine failed
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError>;
+    /// Statistics on the searches of the game
+    fn report(&self) -> Option<String> {
+        None
+    }
 }
 
 fn new_player_api(
-    strategy: Strategy,
+    strategy: &Strategy,
+    playstyle: PlayStyle,
+    player: Case,
+    board: &Board,
+    book: &Option<(Arc<Book>, BookChoice)>,
+    evaluator: Option<Arc<dyn Evaluator>>,
+) -> Result<Box<dyn PlayerApiTrait>, OthelloError> {
+    let engine = new_engine_api(strategy, playstyle, player, board, evaluator)?;
+    Ok(match (strategy, book) {
+        (
+            Strategy::Minimax { .. } | Strategy::MinimaxTimed { .. } | Strategy::MCTS { .. },
+            Some((book, choice)),
+        ) => Box::new(BookPlayerAPI {
+            book: book.clone(),
+            choice: *choice,
+            engine,
+        }),
+        _ => engine,
+    })
+}
+
+/// Evaluator of a minimax strategy, `None` for the other strategies
+fn build_evaluator(strategy: &Strategy) -> Result<Option<Arc<dyn Evaluator>>, OthelloError> {
+    Ok(match strategy {
+        Strategy::Minimax { evaluator, .. } => Some(evaluator.build()?),
+        Strategy::MinimaxTimed { config, .. } => Some(config.evaluator.build()?),
+        _ => None,
+    })
+}
+
+/// Create the engine of a strategy
+/// # Arguments
+/// * `evaluator` - The evaluator of a minimax strategy shared with its other games,
+///   built from the strategy when `None`
+fn new_engine_api(
+    strategy: &Strategy,
     playstyle: PlayStyle,
     player: Case,
     board: &Board,
-) -> Box<dyn PlayerApiTrait> {
-    match strategy {
+    evaluator: Option<Arc<dyn Evaluator>>,
+) -> Result<Box<dyn PlayerApiTrait>, OthelloError> {
+    let shared = |kind: &EvaluatorKind| evaluator.clone().map_or_else(|| kind.build(), Ok);
+    Ok(match strategy.clone() {
         Strategy::Random => Box::new(RandomPlayerAPI),
         Strategy::Greedy => Box::new(GreedyPlayerAPI),
         Strategy::Manual => {
             if matches!(playstyle, PlayStyle::Automatic) {
-                panic!("Manual is not supported in automatic games !");
+                return Err(OthelloError::ManualInAutomatic);
             }
             Box::new(ManualPlayerAPI)
         }
-        Strategy::Minimax { depth } => Box::new(MinimaxPlayerAPI::new(depth, board)),
+        Strategy::Minimax {
+            depth,
+            evaluator,
+            solve_empties,
+            threads,
+        } => Box::new(MinimaxPlayerAPI::new(
+            SearchLimit::Depth(depth),
+            Searcher::with_evaluator(
+                TABLE_BITS,
+                SearchConfig {
+                    evaluator: evaluator.clone(),
+                    threads,
+                    ..SearchConfig::default()
+                },
+                shared(&evaluator)?,
+            ),
+            solve_empties,
+        )),
+        Strategy::MinimaxTimed {
+            move_time,
+            game_time,
+            config,
+            solve_empties,
+        } => Box::new(MinimaxPlayerAPI::new(
+            SearchLimit::Time {
+                move_time,
+                remaining: game_time,
+            },
+            Searcher::with_evaluator(TABLE_BITS, config.clone(), shared(&config.evaluator)?),
+            solve_empties,
+        )),
         Strategy::MCTS {
             playout_budget,
             final_solve,
+            solve_empties,
+            wld_empties,
         } => Box::new(MctsPlayerAPI::new(
             playout_budget,
             final_solve,
+            solve_empties,
+            wld_empties,
             player,
             board,
         )),
+    })
+}
+
+/// Solve the end of the game exactly once at most `solve_empties` squares are left
+fn endgame_move(board: &Board, solve_empties: u8) -> Option<(usize, usize)> {
+    if board.empties() > solve_empties as u32 {
+        return None;
     }
+    EndgameSolver::new().best_move(board).0
 }
 
-struct MctsPlayerAPI(mcts::MCTS);
+/// Player looking its moves up in the opening book, and asking its engine once out of it
+struct BookPlayerAPI {
+    book: Arc<Book>,
+    choice: BookChoice,
+    engine: Box<dyn PlayerApiTrait>,
+}
+
+impl PlayerApiTrait for BookPlayerAPI {
+    #[inline]
+    fn update_board(&mut self, board: &Board) {
+        self.engine.update_board(board);
+    }
+
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        match self.book.choose(board, self.choice) {
+            Some(bmove) => Ok(bmove),
+            None => self.engine.get_move(board),
+        }
+    }
+
+    fn report(&self) -> Option<String> {
+        self.engine.report()
+    }
+}
+
+struct MctsPlayerAPI(mcts::MCTS, u8);
 
 impl MctsPlayerAPI {
     #[inline]
-    fn new(playout_budget: usize, final_solve: bool, player: Case, board: &Board) -> Self {
-        Self(mcts::MCTS::new(
-            player,
-            final_solve,
-            playout_budget,
-            board.clone(),
-        ))
+    fn new(
+        playout_budget: usize,
+        final_solve: bool,
+        solve_empties: u8,
+        wld_empties: u8,
+        player: Case,
+        board: &Board,
+    ) -> Self {
+        Self(
+            mcts::MCTS::new(
+                player,
+                final_solve,
+                playout_budget,
+                wld_empties,
+                board.clone(),
+            ),
+            solve_empties,
+        )
     }
 }
 
@@ -208,8 +552,9 @@ impl PlayerApiTrait for MctsPlayerAPI {
     fn update_board(&mut self, _board: &Board) {}
 
     #[inline]
-    fn get_move(&mut self, board: &Board) -> (usize, usize) {
-        self.0.search(board)
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        // The tree catches up with the game by itself on the next search
+        endgame_move(board, self.1).map_or_else(|| self.0.search(board), Ok)
     }
 }
 
@@ -219,84 +564,118 @@ impl PlayerApiTrait for ManualPlayerAPI {
     #[inline]
     fn update_board(&mut self, _board: &Board) {}
 
-    fn get_move(&mut self, board: &Board) -> (usize, usize) {
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        board.can_move()?;
         let moves = board.available_moves(None);
         println!(
             "Available moves: {}",
             moves
                 .iter()
-                .map(print_coords)
+                .map(|&m| Move(Some(m)).to_string())
                 .collect::<Vec<String>>()
                 .join(", ")
         );
         loop {
             // Get user input with std::io
             let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
+            match std::io::stdin().read_line(&mut input) {
+                Ok(0) => return Err(OthelloError::Io("end of input".to_string())),
+                Ok(_) => {}
+                Err(e) => return Err(OthelloError::Io(e.to_string())),
+            }
             let input = input.trim();
-            let input = input
-                .split("")
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<&str>>();
-            // Input can be A1 or a1
             if input.is_empty() {
                 continue;
             }
-            if input.len() != 2 {
-                if input[0] == "q" {
-                    exit(130);
-                }
-                println!("Invalid move");
-                continue;
+            if input == "q" {
+                exit(130);
             }
-            let input = (
-                input[0].to_uppercase().chars().next().unwrap() as usize - 65,
-                input[1].parse::<usize>().unwrap() - 1,
-            );
-            if !moves.contains(&input) {
-                println!("Invalid move");
-                continue;
+            // Input can be f5 or F5
+            match input.parse::<Move>() {
+                Ok(Move(Some(m))) if moves.contains(&m) => break Ok(m),
+                _ => println!("Invalid move"),
             }
-            break (input);
         }
     }
 }
 
-struct MinimaxPlayerAPI(Tree, u8);
+/// Number of entries of the transposition table of a minimax player, as a power of 2
+const TABLE_BITS: u32 = 18;
+
+enum SearchLimit {
+    Depth(u8),
+    /// Time per move, and time left on the clock for the rest of the game
+    Time {
+        move_time: Duration,
+        remaining: Option<Duration>,
+    },
+}
+
+struct MinimaxPlayerAPI {
+    searcher: Searcher,
+    limit: SearchLimit,
+    solve_empties: u8,
+    nodes: u64,
+}
 
 impl MinimaxPlayerAPI {
     #[inline]
-    fn new(depth: u8, board: &Board) -> Self {
-        Self(Tree::from_board(&mut board.clone(), None, depth), depth)
+    fn new(limit: SearchLimit, searcher: Searcher, solve_empties: u8) -> Self {
+        Self {
+            searcher,
+            limit,
+            solve_empties,
+            nodes: 0,
+        }
     }
 }
 
-impl PlayerApiTrait for MinimaxPlayerAPI {
-    #[inline]
-    fn update_board(&mut self, board: &Board) {
-        if self.1 <= 2 {
-            self.0 = Tree::from_board(&mut board.clone(), None, self.1);
+/// Share the time left on the clock between the moves we still have to play
+fn move_budget(board: &Board, move_time: Duration, remaining: Option<Duration>) -> Duration {
+    match remaining {
+        Some(remaining) => {
+            let moves_left = board.empties().div_ceil(2).max(1);
+            move_time.min(remaining / moves_left)
         }
+        None => move_time,
     }
+}
+
+impl PlayerApiTrait for MinimaxPlayerAPI {
     #[inline]
-    fn get_move(&mut self, board: &Board) -> (usize, usize) {
-        if self.1 > 2 {
-            if let Some(m) = board.history.moves.last() {
-                for i in 0..self.0.moves {
-                    if self.0.subtree.as_ref().unwrap()[i].mov.unwrap() == *m {
-                        let subt = self.0.subtree.as_ref().unwrap()[i].clone();
-                        self.0.subtree = subt.subtree;
-                        self.0.moves = subt.moves;
-                        self.0.cases = subt.cases;
-                        self.0.score = subt.score;
-                        self.0.value = subt.value;
-                        self.0.mov = subt.mov;
-                        break;
-                    }
+    fn update_board(&mut self, _board: &Board) {}
+
+    #[inline]
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        board.can_move()?;
+        if let Some(m) = endgame_move(board, self.solve_empties) {
+            return Ok(m);
+        }
+        let result = match &mut self.limit {
+            SearchLimit::Depth(depth) => self.searcher.search(board, *depth)?,
+            SearchLimit::Time {
+                move_time,
+                remaining,
+            } => {
+                let start = Instant::now();
+                let budget = move_budget(board, *move_time, *remaining);
+                let result = self.searcher.search_timed(board, budget, MAX_PLY as u8)?;
+                if let Some(remaining) = remaining {
+                    *remaining = remaining.saturating_sub(start.elapsed());
                 }
+                result
             }
-        }
-        self.0.best_move(board.get_turn(), board, self.1)
+        };
+        self.nodes += result.nodes;
+        (result.best_move).ok_or_else(|| OthelloError::Engine("no move found".to_string()))
+    }
+
+    fn report(&self) -> Option<String> {
+        Some(format!(
+            "Nodes: {}, Transposition table: {}",
+            self.nodes,
+            self.searcher.table_stats()
+        ))
     }
 }
 
@@ -307,11 +686,11 @@ impl PlayerApiTrait for RandomPlayerAPI {
     fn update_board(&mut self, _board: &Board) {}
 
     #[inline]
-    fn get_move(&mut self, board: &Board) -> (usize, usize) {
-        *board
-            .available_moves(None)
-            .choose(&mut rand::thread_rng())
-            .unwrap()
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        board.can_move()?;
+        (board.available_moves(None).choose(&mut rand::thread_rng()))
+            .copied()
+            .ok_or(OthelloError::MustPass)
     }
 }
 
@@ -322,18 +701,11 @@ impl PlayerApiTrait for GreedyPlayerAPI {
     fn update_board(&mut self, _board: &Board) {}
 
     #[inline]
-    fn get_move(&mut self, board: &Board) -> (usize, usize) {
-        board.move_with_highest_gain().unwrap()
+    fn get_move(&mut self, board: &Board) -> Result<(usize, usize), OthelloError> {
+        board.move_with_highest_gain()
     }
 }
 
-pub fn print_coords(c: &(usize, usize)) -> String {
-    // A1
-    let x = c.0 as u8 + 65;
-    let y = c.1 as u8 + 49;
-    format!("{}{}", x as char, y as char)
-}
-
 fn display_score(score: (u32, u32, u32), n: u32, length: usize, strategy: (&Strategy, &Strategy)) {
     let (black, white, draw) = score;
     let l = length as f32;
@@ -371,3 +743,28 @@ fn display_score(score: (u32, u32, u32), n: u32, length: usize, strategy: (&Stra
 fn go_3_lines_up() {
     crossterm::queue!(stdout(), MoveUp(3)).unwrap();
 }
+
+#[test]
+fn shared_evaluator_test() {
+    let path = std::env::temp_dir().join("othello_shared_evaluator_test.bin");
+    crate::pattern::PatternEvaluator::new().save(&path).unwrap();
+    let minimax = Strategy::Minimax {
+        depth: 1,
+        evaluator: EvaluatorKind::Pattern(path.clone()),
+        solve_empties: 0,
+        threads: 1,
+    };
+    let mut player = Player::new((minimax, Strategy::Random));
+    let (black, white, draw) = player.play_games(2, false, 1).unwrap();
+    assert_eq!(black + white + draw, 2);
+    // The weights were read once, the next games do without the file
+    std::fs::remove_file(&path).unwrap();
+    let (black, white, draw) = player.play_games(2, false, 1).unwrap();
+    assert_eq!(black + white + draw, 2);
+    let [shared, none] = player.evaluators().unwrap();
+    assert!(Arc::ptr_eq(
+        &shared.unwrap(),
+        &player.evaluators().unwrap()[0].clone().unwrap()
+    ));
+    assert!(none.is_none());
+}
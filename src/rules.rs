@@ -1,96 +1,221 @@
-use crate::board::{Case, DIRECTIONS};
+/// Shifts and masks for the 8 directions on a bitboard, square `8 * row + col`
+/// being bit `8 * row + col`. The mask removes the bits wrapping around a row.
+const DIRECTIONS: [(i8, u64); 8] = [
+    (8, 0xffff_ffff_ffff_ffff),  // (1, 0)
+    (9, 0xfefe_fefe_fefe_fefe),  // (1, 1)
+    (1, 0xfefe_fefe_fefe_fefe),  // (0, 1)
+    (-7, 0xfefe_fefe_fefe_fefe), // (-1, 1)
+    (-8, 0xffff_ffff_ffff_ffff), // (-1, 0)
+    (-9, 0x7f7f_7f7f_7f7f_7f7f), // (-1, -1)
+    (-1, 0x7f7f_7f7f_7f7f_7f7f), // (0, -1)
+    (7, 0x7f7f_7f7f_7f7f_7f7f),  // (1, -1)
+];
 
-/// Check if a move is legal
+/// Move every disc of `bits` one square in the given direction
+#[inline]
+fn shift(bits: u64, (shift, mask): (i8, u64)) -> u64 {
+    if shift > 0 {
+        (bits << shift) & mask
+    } else {
+        (bits >> -shift) & mask
+    }
+}
+
+/// The four corners of the board
+pub const CORNERS: u64 = 0x8100_0000_0000_0081;
+
+/// Squares around the corners, given away with the corner as long as it is empty:
+/// for each corner, its two C squares along the edges and its X square
+const CORNER_NEIGHBOURS: [(u64, u64); 4] = [
+    (1 << 0, 0x0000_0000_0000_0302),
+    (1 << 7, 0x0000_0000_0000_c040),
+    (1 << 56, 0x0203_0000_0000_0000),
+    (1 << 63, 0x40c0_0000_0000_0000),
+];
+
+/// Lines of the board along one axis, as computed by `lines`
+type Lines = [u64; 15];
+
+/// Every line of the board going in the direction `(dr, dc)`, the unused ones being empty
+const fn lines(dr: isize, dc: isize) -> Lines {
+    let mut lines = [0; 15];
+    let mut square = 0;
+    while square < 64 {
+        let (r, c) = ((square / 8) as isize, (square % 8) as isize);
+        // Numéro de la ligne : constant le long de la direction
+        let line = match (dr, dc) {
+            (0, _) => r,
+            (_, 0) => c,
+            (1, 1) => r - c + 7,
+            _ => r + c,
+        };
+        lines[line as usize] |= 1 << square;
+        square += 1;
+    }
+    lines
+}
+
+/// The four axes of the board: the lines along each of them, the two directions
+/// of `DIRECTIONS` going along it, and the squares with no neighbour on one side
+const AXES: [(Lines, usize, usize, u64); 4] = [
+    (lines(0, 1), 2, 6, 0x8181_8181_8181_8181),
+    (lines(1, 0), 0, 4, 0xff00_0000_0000_00ff),
+    (lines(1, 1), 1, 5, 0xff81_8181_8181_81ff),
+    (lines(1, -1), 3, 7, 0xff81_8181_8181_81ff),
+];
+
+/// Compute the discs of a player which can never be flipped: along each axis,
+/// either the line is full, or the disc lies against the edge or a stable disc of its own
 /// # Arguments
-/// * `board` - The board
-/// * `bmove` - The move to check
-/// * `color` - The color of the player
-/// # Returns
-/// * `true` if the move is legal
-/// * `false` if the move is illegal
-pub fn is_legal_move(board: &[[Case; 8]; 8], bmove: (usize, usize), color: &Case) -> bool {
-    if board[bmove.0][bmove.1] != Case::Empty {
-        return false;
+/// * `player` - The discs of the player
+/// * `opponent` - The discs of the opponent
+pub fn stable_discs(player: u64, opponent: u64) -> u64 {
+    let filled = player | opponent;
+    let mut protected = [0; 4];
+    for (protected, (lines, ..)) in protected.iter_mut().zip(AXES) {
+        for line in lines {
+            if filled & line == line {
+                *protected |= line;
+            }
+        }
     }
-    for direction in DIRECTIONS {
-        if check_direction(board, (bmove.0 as i8, bmove.1 as i8), direction, color) {
-            return true;
+    // Les pions stables ne font que s'ajouter : on itère jusqu'au point fixe
+    let mut stable = 0;
+    loop {
+        let mut candidates = player;
+        for (protected, (_, forward, backward, edge)) in protected.iter().zip(AXES) {
+            candidates &= protected
+                | edge
+                | shift(stable, DIRECTIONS[forward])
+                | shift(stable, DIRECTIONS[backward]);
         }
+        if candidates == stable {
+            return stable;
+        }
+        stable = candidates;
     }
-    false
 }
 
-pub fn is_legal_move_with_gain(
-    board: &[[Case; 8]; 8],
-    bmove: (usize, usize),
-    color: &Case,
-) -> (bool, usize) {
-    if board[bmove.0][bmove.1] != Case::Empty {
-        return (false, 0);
-    }
-    let mut gain = 0;
+/// Squares next to any disc of `bits`, in all 8 directions
+#[inline]
+pub fn neighbours(bits: u64) -> u64 {
+    DIRECTIONS
+        .iter()
+        .fold(0, |res, &direction| res | shift(bits, direction))
+}
+
+/// Squares of `bits` next to an empty corner
+pub fn corner_neighbours(bits: u64, empty: u64) -> u64 {
+    CORNER_NEIGHBOURS
+        .iter()
+        .filter(|(corner, _)| corner & empty != 0)
+        .fold(0, |res, (_, squares)| res | squares & bits)
+}
+
+/// Transpose the board along its a1-h8 diagonal
+pub fn flip_diagonal(mut bits: u64) -> u64 {
+    let t = 0x0f0f_0f0f_0000_0000 & (bits ^ (bits << 28));
+    bits ^= t ^ (t >> 28);
+    let t = 0x3333_0000_3333_0000 & (bits ^ (bits << 14));
+    bits ^= t ^ (t >> 14);
+    let t = 0x5500_5500_5500_5500 & (bits ^ (bits << 7));
+    bits ^ t ^ (t >> 7)
+}
+
+/// Reverse the columns of the board
+pub fn mirror_columns(mut bits: u64) -> u64 {
+    bits = ((bits >> 1) & 0x5555_5555_5555_5555) | ((bits & 0x5555_5555_5555_5555) << 1);
+    bits = ((bits >> 2) & 0x3333_3333_3333_3333) | ((bits & 0x3333_3333_3333_3333) << 2);
+    ((bits >> 4) & 0x0f0f_0f0f_0f0f_0f0f) | ((bits & 0x0f0f_0f0f_0f0f_0f0f) << 4)
+}
+
+/// Bit of a move on the board
+#[inline]
+pub fn square_mask(bmove: (usize, usize)) -> u64 {
+    1 << (bmove.0 * 8 + bmove.1)
+}
+
+/// Move of a square index
+#[inline]
+pub fn square_coords(square: u32) -> (usize, usize) {
+    (square as usize / 8, square as usize % 8)
+}
+
+/// Compute every legal move of a player
+/// # Arguments
+/// * `player` - The discs of the player to move
+/// * `opponent` - The discs of the opponent
+/// # Returns
+/// * The set of squares where the player can play
+pub fn get_moves(player: u64, opponent: u64) -> u64 {
+    let empty = !(player | opponent);
+    let mut moves = 0;
     for direction in DIRECTIONS {
-        let (is_legal, direction_gain) =
-            check_direction_with_gain(board, (bmove.0 as i8, bmove.1 as i8), direction, color);
-        if is_legal {
-            gain += direction_gain;
+        // A line holds at most 6 opponent discs between the move and the closing disc
+        let mut line = shift(player, direction) & opponent;
+        for _ in 0..5 {
+            line |= shift(line, direction) & opponent;
         }
+        moves |= shift(line, direction) & empty;
     }
-    (gain > 0, gain)
+    moves
 }
 
-/// Check if a move is legal in a given direction
+/// Compute the discs flipped by a move
 /// # Arguments
-/// * `board` - The board
-/// * `start` - The starting point of the move
-/// * `direction` - The direction of the move
-/// * `color` - The color of the player
+/// * `player` - The discs of the player to move
+/// * `opponent` - The discs of the opponent
+/// * `bmove` - The bit of the square played, which must be empty
 /// # Returns
-/// * `true` if the move is legal in the given direction
-/// * `false` if the move is illegal in the given direction
-pub fn check_direction(
-    board: &[[Case; 8]; 8],
-    start: (i8, i8),
-    direction: (i8, i8),
-    color: &Case,
-) -> bool {
-    let mut x = start.0 + direction.0;
-    let mut y = start.1 + direction.1;
-    while (0..8).contains(&x)
-        && (0..8).contains(&y)
-        && board[x as usize][y as usize] == color.opponent()
-    {
-        x += direction.0;
-        y += direction.1;
+/// * The set of discs flipped, empty if the move is illegal
+pub fn get_flips(player: u64, opponent: u64, bmove: u64) -> u64 {
+    let mut flips = 0;
+    for direction in DIRECTIONS {
+        let mut line = 0;
+        let mut x = shift(bmove, direction);
+        while x & opponent != 0 {
+            line |= x;
+            x = shift(x, direction);
+        }
+        if x & player != 0 {
+            flips |= line;
+        }
     }
-    (0..8).contains(&x)
-        && (0..8).contains(&y)
-        && board[x as usize][y as usize] == *color
-        && ((x - start.0).abs() > 1 || (y - start.1).abs() > 1)
+    flips
 }
 
-pub fn check_direction_with_gain(
-    board: &[[Case; 8]; 8],
-    start: (i8, i8),
-    direction: (i8, i8),
-    color: &Case,
-) -> (bool, usize) {
-    let mut x = start.0 + direction.0;
-    let mut y = start.1 + direction.1;
-    let mut gain = 0;
-    while (0..8).contains(&x)
-        && (0..8).contains(&y)
-        && board[x as usize][y as usize] == color.opponent()
-    {
-        x += direction.0;
-        y += direction.1;
-        gain += 1;
+/// Check if a move is legal
+/// # Arguments
+/// * `player` - The discs of the player to move
+/// * `opponent` - The discs of the opponent
+/// * `bmove` - The move to check
+/// # Returns
+/// * `true` if the move is legal
+/// * `false` if the move is illegal
+pub fn is_legal_move(player: u64, opponent: u64, bmove: (usize, usize)) -> bool {
+    if bmove.0 >= 8 || bmove.1 >= 8 {
+        return false;
     }
-    (
-        (0..8).contains(&x)
-            && (0..8).contains(&y)
-            && board[x as usize][y as usize] == *color
-            && ((x - start.0).abs() > 1 || (y - start.1).abs() > 1),
-        gain,
-    )
+    let square = square_mask(bmove);
+    (player | opponent) & square == 0 && get_flips(player, opponent, square) != 0
+}
+
+#[test]
+fn get_flips_test() {
+    // Initial position, black to move
+    let black = square_mask((3, 4)) | square_mask((4, 3));
+    let white = square_mask((3, 3)) | square_mask((4, 4));
+    assert_eq!(
+        get_moves(black, white),
+        square_mask((2, 3)) | square_mask((3, 2)) | square_mask((4, 5)) | square_mask((5, 4))
+    );
+    assert_eq!(
+        get_flips(black, white, square_mask((2, 3))),
+        square_mask((3, 3))
+    );
+    assert_eq!(get_flips(black, white, square_mask((0, 0))), 0);
+    // No wrapping from the end of a row to the start of the next one
+    let player = square_mask((0, 0));
+    let opponent = square_mask((0, 7)) | square_mask((1, 0));
+    assert_eq!(get_moves(player, opponent), square_mask((2, 0)));
 }
@@ -0,0 +1,225 @@
+use crate::board::{Board, Case};
+use crate::endgame::EndgameSolver;
+use crate::error::OthelloError;
+use crate::evaluation::EvaluatorKind;
+use crate::minimax::{SearchConfig, Searcher};
+use crate::pattern::{phase, PatternEvaluator, Patterns, PHASES};
+use fxhash::FxHashMap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::io;
+use std::path::Path;
+
+/// Moves of a game, `None` being a pass
+pub type Game = Vec<Option<(usize, usize)>>;
+
+/// Number of empty squares from which the games are solved to label their positions
+const LABEL_SOLVE_EMPTIES: u32 = 14;
+/// Number of random moves opening the self-play games
+const RANDOM_OPENING: usize = 8;
+/// Probability of a random move afterwards
+const RANDOM_MOVE_RATE: f64 = 0.1;
+/// Depth of the searches of the self-play games
+const SELF_PLAY_DEPTH: u8 = 2;
+const LEARNING_RATE: f32 = 0.02;
+/// Occurrences added to those of every weight before averaging its gradient,
+/// so that rare configurations move slowly
+const RARE_CONFIGURATION: f32 = 10.;
+
+/// Position of a game and its final disc differential for the player to move
+pub struct Sample {
+    player: u64,
+    opponent: u64,
+    phase: usize,
+    target: f32,
+}
+
+/// Play games between two minimax players, made different by random moves
+/// # Arguments
+/// * `n` - The number of games
+/// * `rng` - The generator seeding the random moves of each game
+/// # Returns
+/// * `Err(OthelloError)` if a search fails
+pub fn self_play(n: usize, rng: &mut impl Rng) -> Result<Vec<Game>, OthelloError> {
+    let seeds: Vec<u64> = (0..n).map(|_| rng.gen()).collect();
+    seeds
+        .into_par_iter()
+        .map(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let config = SearchConfig {
+                evaluator: EvaluatorKind::Features,
+                ..SearchConfig::default()
+            };
+            let mut searcher = Searcher::with_config(16, config)?;
+            let mut board = Board::new();
+            while !board.is_ended() {
+                let moves = board.available_moves(None);
+                let random =
+                    board.history.moves.len() < RANDOM_OPENING || rng.gen_bool(RANDOM_MOVE_RATE);
+                let bmove = if moves.is_empty() || !random {
+                    searcher.search(&board, SELF_PLAY_DEPTH)?.best_move
+                } else {
+                    moves.choose(&mut rng).copied()
+                };
+                board.play(bmove)?;
+            }
+            Ok(board.history.moves)
+        })
+        .collect()
+}
+
+/// Label the positions of games with the final disc differential: the end of a
+/// game being replaced by perfect play, every position is labeled with the exact
+/// result of its game once few enough squares are left. The positions met in
+/// several games, or symmetric of one another, make one sample with the average result.
+pub fn samples(games: &[Game]) -> Vec<Sample> {
+    let labeled: Vec<(u64, Sample)> = games
+        .par_iter()
+        .flat_map_iter(|game| {
+            let mut board = Board::new();
+            let mut positions = Vec::new();
+            for &bmove in game {
+                if board.empties() <= LABEL_SOLVE_EMPTIES || board.is_ended() {
+                    break;
+                }
+                positions.push(board.clone());
+                board.play(bmove).expect("Game should be legal");
+            }
+            // Partie arrêtée trop tôt pour être résolue : on n'en garde rien
+            let complete = board.empties() <= LABEL_SOLVE_EMPTIES || board.is_ended();
+            if !complete {
+                positions.clear();
+            }
+            // Suite jouée parfaitement : le score final ne change plus
+            let mut solver = EndgameSolver::new();
+            while complete && !board.is_ended() {
+                positions.push(board.clone());
+                let (bmove, _) = solver.best_move(&board);
+                board.play(bmove).expect("Best move should be legal");
+            }
+            let (black, white) = board.score();
+            let black_score = black as f32 - white as f32;
+            positions.into_iter().map(move |position| {
+                let color = position.get_turn();
+                let sign = if color == Case::Black { 1. } else { -1. };
+                // Les évaluateurs étant symétriques, une seule orientation suffit
+                let (canonical, _) = position.canonical();
+                let sample = Sample {
+                    player: canonical.discs(color),
+                    opponent: canonical.discs(color.opponent()),
+                    phase: phase(&position),
+                    target: sign * black_score,
+                };
+                (canonical.zobrist(), sample)
+            })
+        })
+        .collect();
+    // Somme des résultats et nombre d'occurrences de chaque position
+    let mut merged: FxHashMap<u64, (Sample, u32)> = FxHashMap::default();
+    for (key, sample) in labeled {
+        let (merged, count) = merged.entry(key).or_insert((
+            Sample {
+                target: 0.,
+                ..sample
+            },
+            0,
+        ));
+        merged.target += sample.target;
+        *count += 1;
+    }
+    merged
+        .into_values()
+        .map(|(sample, count)| Sample {
+            target: sample.target / count as f32,
+            ..sample
+        })
+        .collect()
+}
+
+/// Fit the weights of a pattern evaluator to the samples by gradient descent
+/// on the squared error, each weight moving by its average gradient
+/// # Arguments
+/// * `epochs` - The number of passes over the samples
+/// # Returns
+/// * The evaluator, and its mean squared error in discs at each epoch, before its update
+pub fn fit(samples: &[Sample], epochs: usize) -> (PatternEvaluator, Vec<f32>) {
+    let patterns = Patterns::new();
+    let mut weights = vec![0_f32; patterns.size * PHASES];
+    let mut occurrences = vec![0_f32; weights.len()];
+    for sample in samples {
+        let offset = sample.phase * patterns.size;
+        for index in patterns.indices(sample.player, sample.opponent) {
+            occurrences[offset + index] += 1.;
+        }
+    }
+    let mut errors = Vec::with_capacity(epochs);
+    for _ in 0..epochs {
+        let mut gradient = vec![0_f32; weights.len()];
+        let mut squared_error = 0.;
+        for sample in samples {
+            let offset = sample.phase * patterns.size;
+            let indices = patterns.indices(sample.player, sample.opponent);
+            let prediction: f32 = indices.map(|index| weights[offset + index]).sum();
+            let error = sample.target - prediction;
+            squared_error += error * error;
+            for index in patterns.indices(sample.player, sample.opponent) {
+                gradient[offset + index] += error;
+            }
+        }
+        for ((weight, gradient), occurrences) in weights.iter_mut().zip(gradient).zip(&occurrences)
+        {
+            *weight += LEARNING_RATE * gradient / (occurrences + RARE_CONFIGURATION);
+        }
+        errors.push(squared_error / samples.len().max(1) as f32);
+    }
+    let mut evaluator = PatternEvaluator::new();
+    for (stored, weight) in evaluator.weights.iter_mut().zip(weights) {
+        // Centièmes de pion
+        *stored = (weight * 100.)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+    (evaluator, errors)
+}
+
+/// Train the pattern evaluator on games and write its weights
+/// # Arguments
+/// * `games` - The games, from self-play or a database
+/// * `epochs` - The number of passes of the gradient descent
+/// * `path` - The weights file to write
+pub fn train(games: &[Game], epochs: usize, path: impl AsRef<Path>) -> io::Result<()> {
+    let samples = samples(games);
+    println!("Fitting the weights on {} positions...", samples.len());
+    let (evaluator, errors) = fit(&samples, epochs);
+    for (epoch, error) in errors.iter().enumerate() {
+        println!("Epoch {}: mean squared error {:.2}", epoch + 1, error);
+    }
+    evaluator.save(path)
+}
+
+#[test]
+fn fit_test() {
+    let games = self_play(4, &mut StdRng::seed_from_u64(0)).unwrap();
+    let samples = samples(&games);
+    assert!(samples.len() >= 4 * 40);
+    // Every game starts from the same position, which makes a single sample
+    let starts = samples
+        .iter()
+        .filter(|s| (s.player | s.opponent).count_ones() == 4);
+    assert_eq!(starts.count(), 1);
+    // The first epoch starts from zero weights, giving the variance of the targets
+    let (evaluator, errors) = fit(&samples, 20);
+    let variance = samples.iter().map(|s| s.target * s.target).sum::<f32>() / samples.len() as f32;
+    assert_eq!(errors[0], variance);
+    assert!(errors[19] < errors[0] / 2.);
+    let fitted = samples.iter().filter(|sample| {
+        let offset = sample.phase * evaluator.patterns.size;
+        let value: i32 = (evaluator.patterns.indices(sample.player, sample.opponent))
+            .map(|index| evaluator.weights[offset + index] as i32)
+            .sum();
+        (value as f32 / 100. - sample.target).abs() < sample.target.abs().max(1.)
+    });
+    assert!(fitted.count() > samples.len() / 2);
+}
@@ -0,0 +1,232 @@
+use std::fmt::{Display, Formatter};
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// What the value of an entry says about the real value of the position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The real value is at least the stored one (beta cutoff)
+    Lower,
+    /// The real value is at most the stored one (no move reached alpha)
+    Upper,
+}
+
+impl Bound {
+    /// Bound of a value found by a fail-soft alpha-beta search with the window (alpha, beta)
+    pub fn from_window(value: i32, alpha: i32, beta: i32) -> Bound {
+        if value <= alpha {
+            Bound::Upper
+        } else if value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub value: i32,
+    pub bound: Bound,
+    /// Depth of the search that produced the value
+    pub depth: u8,
+    pub best_move: Option<(usize, usize)>,
+    /// Search in which the entry was written
+    generation: u8,
+}
+
+/// Set in the data of every written slot, telling it apart from an empty one
+const OCCUPIED: u64 = 1 << 63;
+
+impl Entry {
+    /// Returns the value of the entry if it is enough to answer a search
+    /// of `depth` with the window (alpha, beta)
+    pub fn cutoff(&self, depth: u8, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.value),
+            Bound::Lower if self.value >= beta => Some(self.value),
+            Bound::Upper if self.value <= alpha => Some(self.value),
+            _ => None,
+        }
+    }
+
+    /// Everything but the key in one word: value, depth, bound, move and generation
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let best_move = self.best_move.map_or(0, |(r, c)| r * 8 + c + 1) as u64;
+        OCCUPIED
+            | self.value as u32 as u64
+            | (self.depth as u64) << 32
+            | bound << 40
+            | best_move << 42
+            | (self.generation as u64) << 49
+    }
+
+    fn unpack(data: u64) -> Entry {
+        let bound = match (data >> 40) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best_move = match (data >> 42) & 0x7f {
+            0 => None,
+            m => Some(((m as usize - 1) / 8, (m as usize - 1) % 8)),
+        };
+        Entry {
+            value: data as u32 as i32,
+            bound,
+            depth: (data >> 32) as u8,
+            best_move,
+            generation: (data >> 49) as u8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TableStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub cutoffs: u64,
+    pub stores: u64,
+}
+
+impl AddAssign for TableStats {
+    fn add_assign(&mut self, other: TableStats) {
+        self.probes += other.probes;
+        self.hits += other.hits;
+        self.cutoffs += other.cutoffs;
+        self.stores += other.stores;
+    }
+}
+
+impl Display for TableStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let hit_rate = if self.probes == 0 {
+            0.
+        } else {
+            self.hits as f64 / self.probes as f64 * 100.
+        };
+        write!(
+            f,
+            "probes: {}, hits: {} ({:.2} %), cutoffs: {}, stores: {}",
+            self.probes, self.hits, hit_rate, self.cutoffs, self.stores
+        )
+    }
+}
+
+/// Fixed-size hash table of searched positions indexed by their Zobrist key.
+/// An entry is replaced by a deeper search, or by any search once it is
+/// left over from the previous moves of the game.
+///
+/// The table is shared by the threads of a search without any lock: each slot holds
+/// the data of an entry and its key xored with the data, so that an entry torn by
+/// two concurrent writes no longer matches its key and is simply missed.
+#[derive(Debug)]
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Create a table of `2^bits` entries
+    pub fn new(bits: u32) -> Self {
+        TranspositionTable {
+            entries: (0..1 << bits)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    #[inline]
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    /// Mark the start of a new search, making the current entries replaceable
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Look up a position
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let [check, data] = &self.entries[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
+        if data & OCCUPIED == 0 || check.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        Some(Entry::unpack(data))
+    }
+
+    /// Save the result of the search of a position
+    /// # Returns
+    /// * Whether the entry was written, a deeper one of the same search being kept
+    pub fn store(
+        &self,
+        key: u64,
+        depth: u8,
+        value: i32,
+        bound: Bound,
+        best_move: Option<(usize, usize)>,
+    ) -> bool {
+        let [check, data] = &self.entries[self.index(key)];
+        let generation = self.generation.load(Ordering::Relaxed);
+        let old = data.load(Ordering::Relaxed);
+        if old & OCCUPIED != 0 {
+            let old = Entry::unpack(old);
+            if old.generation == generation && old.depth > depth {
+                return false;
+            }
+        }
+        let entry = Entry {
+            value,
+            bound,
+            depth,
+            best_move,
+            generation,
+        }
+        .pack();
+        check.store(key ^ entry, Ordering::Relaxed);
+        data.store(entry, Ordering::Relaxed);
+        true
+    }
+}
+
+#[test]
+fn replace_by_depth_test() {
+    let table = TranspositionTable::new(4);
+    assert!(table.store(1, 5, 10, Bound::Exact, Some((2, 3))));
+    // Same slot, shallower search
+    assert!(!table.store(17, 3, 20, Bound::Lower, None));
+    assert!(table.probe(17).is_none());
+    let entry = table.probe(1).expect("Entry should be kept");
+    assert_eq!(entry.best_move, Some((2, 3)));
+    assert_eq!(entry.cutoff(5, 0, 100), Some(10));
+    assert_eq!(entry.cutoff(6, 0, 100), None);
+    // Entries of the previous search can always be replaced
+    table.new_search();
+    assert!(table.store(17, 3, -20, Bound::Lower, None));
+    let entry = table.probe(17).expect("Entry should be replaced");
+    assert_eq!(entry.cutoff(3, -30, -25), Some(-20));
+    assert_eq!(entry.cutoff(3, -30, -15), None);
+    assert!(table.probe(1).is_none());
+}
+
+#[test]
+fn torn_entry_test() {
+    let table = TranspositionTable::new(4);
+    table.store(1, 5, 10, Bound::Exact, Some((7, 7)));
+    // Data of another entry written over the slot without its key
+    let other = Entry::unpack(0).pack() | 1;
+    table.entries[1][1].store(other, Ordering::Relaxed);
+    assert!(table.probe(1).is_none());
+    assert!(table.probe(17).is_none());
+}
@@ -0,0 +1,143 @@
+use crate::board::{Board, BoardState};
+use crate::error::{LoadError, OthelloError};
+use crate::train::Game;
+use std::fs;
+use std::path::Path;
+
+/// Size of the header of a WTHOR file
+const HEADER_SIZE: usize = 16;
+/// Size of a game record: indices, scores and 60 moves
+const GAME_SIZE: usize = 68;
+
+/// Game of a WTHOR database (`.wtb`), as played in a tournament of the French federation
+#[derive(Clone, Debug, PartialEq)]
+pub struct WthorGame {
+    /// Index of the tournament in the `.TRN` file
+    pub tournament: u16,
+    /// Indices of the players in the `.JOU` file
+    pub black: u16,
+    pub white: u16,
+    /// Final number of discs of Black, the empty squares going to the winner
+    pub black_discs: u8,
+    /// Number of discs of Black with perfect play from the position computed
+    /// by the federation, some empties before the end
+    pub theoretical_discs: u8,
+    /// Moves of the game, passes included
+    pub moves: Game,
+}
+
+impl WthorGame {
+    /// Final disc differential for Black
+    pub fn score(&self) -> i32 {
+        2 * self.black_discs as i32 - 64
+    }
+}
+
+/// Read the games of a WTHOR file
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<WthorGame>, LoadError> {
+    Ok(parse(&fs::read(path)?)?)
+}
+
+/// Read the games of the content of a WTHOR file
+/// # Returns
+/// * `Err(OthelloError::Parse)` if the file is truncated, is not of an 8x8 board,
+///   or holds an illegal game
+pub fn parse(bytes: &[u8]) -> Result<Vec<WthorGame>, OthelloError> {
+    let invalid = OthelloError::Parse;
+    if bytes.len() < HEADER_SIZE {
+        return Err(invalid("Not a WTHOR file: no header".to_string()));
+    }
+    let count = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    // Taille du plateau, 0 valant 8 dans les anciens fichiers
+    if !matches!(bytes[12], 0 | 8) {
+        return Err(invalid(format!("Unsupported board size {}", bytes[12])));
+    }
+    let records = &bytes[HEADER_SIZE..];
+    if records.len() < count * GAME_SIZE {
+        return Err(invalid(format!(
+            "Truncated WTHOR file: {} games announced, {} found",
+            count,
+            records.len() / GAME_SIZE
+        )));
+    }
+    records
+        .chunks_exact(GAME_SIZE)
+        .take(count)
+        .enumerate()
+        .map(|(i, record)| {
+            let word = |at: usize| u16::from_le_bytes([record[at], record[at + 1]]);
+            let moves = read_moves(&record[8..])
+                .map_err(|e| invalid(format!("Game {} of the file: {}", i + 1, e)))?;
+            Ok(WthorGame {
+                tournament: word(0),
+                black: word(2),
+                white: word(4),
+                black_discs: record[6],
+                theoretical_discs: record[7],
+                moves,
+            })
+        })
+        .collect()
+}
+
+/// Replay the moves of a record, each written `10 * row + column` counting from 1
+/// and 0 after the last one, adding the passes it leaves out
+fn read_moves(bytes: &[u8]) -> Result<Game, OthelloError> {
+    let mut board = Board::new();
+    for &byte in bytes.iter().take_while(|&&byte| byte != 0) {
+        let (row, col) = (byte / 10, byte % 10);
+        if !(1..=8).contains(&row) || !(1..=8).contains(&col) {
+            return Err(OthelloError::Parse(format!("Invalid move {}", byte)));
+        }
+        if board.get_state() == BoardState::MustPass {
+            board.pass()?;
+        }
+        board.play(Some((row as usize - 1, col as usize - 1)))?;
+    }
+    Ok(board.history.moves)
+}
+
+#[test]
+fn wthor_test() {
+    // Game with a pass, and a shorter one
+    let mut board = Board::new();
+    while !board.is_ended() {
+        let moves = board.available_moves(None);
+        board.play(moves.last().copied()).unwrap();
+    }
+    assert!(board.history.moves.contains(&None));
+    let short = Board::from_transcript("f5d6c3d3c4").unwrap();
+
+    let mut bytes = vec![0; HEADER_SIZE];
+    bytes[4] = 2;
+    for (i, game) in [&board, &short].into_iter().enumerate() {
+        let mut record = vec![0; GAME_SIZE];
+        record[0] = 7;
+        record[2] = i as u8;
+        record[4] = 3;
+        record[6] = game.score().0 as u8;
+        let squares = game.history.moves.iter().flatten();
+        for (byte, &(row, col)) in record[8..].iter_mut().zip(squares) {
+            *byte = (10 * (row + 1) + col + 1) as u8;
+        }
+        bytes.extend(record);
+    }
+    let games = parse(&bytes).unwrap();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].moves, board.history.moves);
+    assert_eq!(games[0].black_discs as usize, board.score().0);
+    assert_eq!(games[1].score(), 2 * short.score().0 as i32 - 64);
+    assert_eq!(
+        (games[1].tournament, games[1].black, games[1].white),
+        (7, 1, 3)
+    );
+    assert_eq!(games[1].moves, short.history.moves);
+
+    assert!(parse(&bytes[..HEADER_SIZE + GAME_SIZE]).is_err());
+    let mut illegal = bytes.clone();
+    illegal[HEADER_SIZE + 8] = 11;
+    assert!(parse(&illegal).is_err());
+    let mut ten = bytes;
+    ten[12] = 10;
+    assert!(matches!(parse(&ten), Err(OthelloError::Parse(_))));
+}